@@ -1,11 +1,12 @@
 use async_std::task;
 use chrono::prelude::*;
+use std::collections::HashMap;
 use std::time::Duration;
 
 use horoscope::executor::Executor;
 use horoscope::job::network::{Job, NetType};
 use horoscope::logger::Logger;
-use horoscope::scheduler::{blocking, daemon, Msg, Schedule};
+use horoscope::scheduler::{blocking, daemon, DaemonOptions, Msg, Schedule};
 use horoscope::store::Store;
 
 #[async_std::main]
@@ -18,11 +19,11 @@ async fn main() {
     start_time
   };
 
-  let logger = Logger::new(true, vec![]);
+  let logger = Logger::new(true, vec![]).with_banners();
   let mut blk_scheduler =
     blocking::Scheduler::new(String::from("blk_scheduler"), Some(logger));
 
-  blk_scheduler.load_snapshot_from_disk();
+  blk_scheduler.load_snapshot_from_disk().await;
 
   let store = Store::new(String::from("jobStore-test"));
   let exec = Executor::new(String::from("executor-test"));
@@ -30,7 +31,7 @@ async fn main() {
     String::from("job-1"),
     String::from("https://ping.me/"),
     NetType::Get,
-    None,
+    HashMap::new(),
     None,
   );
 
@@ -40,6 +41,7 @@ async fn main() {
     .unwrap();
   blk_scheduler
     .add_executor(String::from("executor-test"), exec)
+    .await
     .unwrap();
   blk_scheduler
     .add_job(
@@ -50,9 +52,14 @@ async fn main() {
       None,
       Box::new(njob),
     )
+    .await
     .unwrap();
 
-  let (sender, _reader) = daemon(Box::new(blk_scheduler), true);
+  let options = DaemonOptions {
+    save_state: true,
+    ..DaemonOptions::default()
+  };
+  let (sender, _reader) = daemon(Box::new(blk_scheduler), options).unwrap();
 
   match sender
     .send(Msg::Log(
@@ -70,7 +77,7 @@ async fn main() {
     String::from("job-2"),
     String::from("https://ping.me/"),
     NetType::Get,
-    None,
+    HashMap::new(),
     None,
   );
 
@@ -0,0 +1,347 @@
+use async_channel::{self, Sender};
+use async_std::net::TcpListener;
+use async_std::prelude::*;
+use async_std::task;
+use http_types::{Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::executor::Executor;
+use crate::job::{Misfire, Status, Work};
+use crate::ledger::Query;
+use crate::scheduler::{Msg, Read};
+use crate::store::Store;
+use crate::trigger::Trigger;
+
+// How long to wait before accepting again after failing to.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize)]
+struct NewStore {
+  alias: String,
+}
+
+#[derive(Deserialize)]
+struct NewJob {
+  alias: String,
+  executor: String,
+  start_time: i64,
+  end_time: Option<i64>,
+  job: Box<dyn Work>,
+}
+
+// The body of `PUT /stores/{store}/jobs/{job}`, the alias comes from the
+// path.
+#[derive(Deserialize)]
+struct JobUpdate {
+  executor: String,
+  start_time: i64,
+  end_time: Option<i64>,
+  job: Box<dyn Work>,
+}
+
+// Filters of `GET /runs`, statuses are given by name.
+#[derive(Deserialize)]
+struct Runs {
+  store: Option<String>,
+  job: Option<String>,
+  status: Option<String>,
+  from: Option<i64>,
+  to: Option<i64>,
+  newest_first: Option<bool>,
+  offset: Option<usize>,
+  limit: Option<usize>,
+}
+
+impl Runs {
+  fn query(self) -> Result<Query, String> {
+    let status = match self.status.as_deref() {
+      None => None,
+      Some("Waiting") => Some(Status::Waiting),
+      Some("Running") => Some(Status::Running),
+      Some("Paused") => Some(Status::Paused),
+      Some("Success") => Some(Status::Success),
+      Some("Failure") => Some(Status::Failure(String::new())),
+      Some("Missed") => Some(Status::Missed),
+      Some(status) => return Err(format!("Unknown status {}", status)),
+    };
+    Ok(Query {
+      store: self.store,
+      job: self.job,
+      status,
+      from: self.from,
+      to: self.to,
+      newest_first: self.newest_first.unwrap_or(false),
+      offset: self.offset.unwrap_or(0),
+      limit: self.limit,
+    })
+  }
+}
+
+// What a request asks of the scheduler.
+enum Action {
+  Read(Read),
+  Write(Msg),
+}
+
+fn json<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, String> {
+  serde_json::from_str(body).map_err(|e| format!("Invalid body: {}", e))
+}
+
+fn route(req: &Request, body: &str) -> Result<Action, (StatusCode, String)> {
+  let path: Vec<String> = req
+    .url()
+    .path_segments()
+    .map(|segments| {
+      segments
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+    })
+    .unwrap_or_default();
+  let path: Vec<&str> = path.iter().map(String::as_str).collect();
+  let bad = |e: String| (StatusCode::BadRequest, e);
+
+  let action = match (req.method(), path.as_slice()) {
+    (Method::Get, ["health"]) => Action::Read(Read::Health),
+    (Method::Get, ["runs"]) => {
+      let runs: Runs = req.query().map_err(|e| bad(format!("{}", e)))?;
+      Action::Read(Read::Runs(runs.query().map_err(bad)?))
+    }
+
+    (Method::Get, ["stores"]) => Action::Read(Read::Stores),
+    (Method::Post, ["stores"]) => {
+      let store: NewStore = json(body).map_err(bad)?;
+      Action::Write(Msg::AddStore(store.alias.clone(), Store::new(store.alias)))
+    }
+    (Method::Delete, ["stores", store]) => {
+      Action::Write(Msg::RemoveStore(store.to_string()))
+    }
+
+    (Method::Get, ["executors"]) => Action::Read(Read::Executors),
+    (Method::Post, ["executors"]) => {
+      let executor: Executor = json(body).map_err(bad)?;
+      Action::Write(Msg::AddExecutor(executor.alias.clone(), executor))
+    }
+    (Method::Delete, ["executors", executor]) => {
+      Action::Write(Msg::RemoveExecutor(executor.to_string()))
+    }
+
+    (Method::Get, ["stores", store, "jobs"]) => {
+      Action::Read(Read::Jobs(store.to_string()))
+    }
+    (Method::Post, ["stores", store, "jobs"]) => {
+      let job: NewJob = json(body).map_err(bad)?;
+      Action::Write(Msg::AddJob(
+        job.alias,
+        store.to_string(),
+        job.executor,
+        job.start_time,
+        job.end_time,
+        job.job,
+      ))
+    }
+    (Method::Get, ["stores", store, "jobs", job]) => {
+      Action::Read(Read::Job(store.to_string(), job.to_string()))
+    }
+    (Method::Put, ["stores", store, "jobs", job]) => {
+      let update: JobUpdate = json(body).map_err(bad)?;
+      Action::Write(Msg::ModifyJob(
+        job.to_string(),
+        store.to_string(),
+        update.executor,
+        update.start_time,
+        update.end_time,
+        update.job,
+      ))
+    }
+    (Method::Delete, ["stores", store, "jobs", job]) => {
+      Action::Write(Msg::RemoveJob(job.to_string(), store.to_string()))
+    }
+    (Method::Put, ["stores", store, "jobs", job, "misfire"]) => {
+      let misfire: Option<Misfire> = json(body).map_err(bad)?;
+      Action::Write(Msg::SetMisfire(
+        job.to_string(),
+        store.to_string(),
+        misfire,
+      ))
+    }
+    (Method::Put, ["stores", store, "jobs", job, "timeout"]) => {
+      let timeout: Option<i64> = json(body).map_err(bad)?;
+      Action::Write(Msg::SetTimeout(
+        job.to_string(),
+        store.to_string(),
+        timeout,
+      ))
+    }
+    (Method::Post, ["stores", store, "jobs", job, "pause"]) => {
+      Action::Write(Msg::PauseJob(job.to_string(), store.to_string()))
+    }
+    (Method::Post, ["stores", store, "jobs", job, "resume"]) => {
+      Action::Write(Msg::ResumeJob(job.to_string(), store.to_string()))
+    }
+    (Method::Post, ["stores", store, "jobs", job, "run"]) => {
+      Action::Write(Msg::RunJob(store.to_string(), job.to_string()))
+    }
+    (Method::Post, ["stores", store, "jobs", job, "cancel"]) => {
+      Action::Write(Msg::CancelRun(store.to_string(), job.to_string()))
+    }
+
+    (Method::Get, ["stores", store, "jobs", job, "triggers"]) => {
+      Action::Read(Read::Triggers(store.to_string(), job.to_string()))
+    }
+    (Method::Post, ["stores", store, "jobs", job, "triggers"]) => {
+      let trigger: Trigger = json(body).map_err(bad)?;
+      Action::Write(Msg::AddTrigger(
+        job.to_string(),
+        store.to_string(),
+        trigger,
+      ))
+    }
+    (Method::Put, ["stores", store, "jobs", job, "triggers", trigger]) => {
+      let mut update: Trigger = json(body).map_err(bad)?;
+      update.alias = trigger.to_string();
+      Action::Write(Msg::ModifyTrigger(
+        job.to_string(),
+        store.to_string(),
+        update,
+      ))
+    }
+    (Method::Delete, ["stores", store, "jobs", job, "triggers", trigger]) => {
+      Action::Write(Msg::RemoveTrigger(
+        job.to_string(),
+        store.to_string(),
+        trigger.to_string(),
+      ))
+    }
+    _ => {
+      return Err((
+        StatusCode::NotFound,
+        format!("No route for {} {}", req.method(), req.url().path()),
+      ))
+    }
+  };
+  Ok(action)
+}
+
+fn respond(status: StatusCode, body: Option<Value>) -> Response {
+  let mut res = Response::new(status);
+  if let Some(body) = body {
+    res.set_body(body);
+  }
+  res
+}
+
+fn error(status: StatusCode, e: String) -> Response {
+  respond(status, Some(json!({ "error": e })))
+}
+
+// Compares the request's bearer token with `token`, looking at every byte so
+// the time taken doesn't tell how much of it matched.
+fn authorized(req: &Request, token: &str) -> bool {
+  let given = match req.header("Authorization") {
+    Some(values) => values.last().as_str().to_string(),
+    None => return false,
+  };
+  let expected = format!("Bearer {}", token);
+  given.len() == expected.len()
+    && given
+      .bytes()
+      .zip(expected.bytes())
+      .fold(0, |diff, (a, b)| diff | (a ^ b))
+      == 0
+}
+
+// Whether a scheduler error means the store, job, trigger or executor
+// addressed doesn't exist.
+fn missing(e: &str) -> bool {
+  e.contains("not found") || e.contains("doesn't exist")
+}
+
+// Reads answer 200 with their JSON or 404, writes answer 204, or 404 when
+// what they address doesn't exist and 400 otherwise, all with the
+// scheduler's error as `{"error": ...}`. Requests without the token answer
+// 401.
+async fn handle(
+  mut req: Request,
+  sender: Sender<Msg>,
+  token: &str,
+) -> Response {
+  if !authorized(&req, token) {
+    let mut res =
+      error(StatusCode::Unauthorized, String::from("Invalid admin token"));
+    res.insert_header("WWW-Authenticate", "Bearer");
+    return res;
+  }
+  let body = match req.body_string().await {
+    Ok(body) => body,
+    Err(e) => return error(StatusCode::BadRequest, format!("{}", e)),
+  };
+  let action = match route(&req, &body) {
+    Ok(action) => action,
+    Err((status, e)) => return error(status, e),
+  };
+  let stopped = || {
+    error(
+      StatusCode::ServiceUnavailable,
+      String::from("Scheduler is not running"),
+    )
+  };
+  match action {
+    Action::Read(read) => {
+      let (s, r) = async_channel::bounded(1);
+      if sender.send(Msg::Read(read, s)).await.is_err() {
+        return stopped();
+      }
+      match r.recv().await {
+        Ok(Ok(value)) => respond(StatusCode::Ok, Some(value)),
+        Ok(Err(e)) => error(StatusCode::NotFound, e),
+        Err(_) => stopped(),
+      }
+    }
+    Action::Write(msg) => {
+      let (s, r) = async_channel::bounded(1);
+      if sender.send(Msg::Reply(Box::new(msg), s)).await.is_err() {
+        return stopped();
+      }
+      match r.recv().await {
+        Ok(Ok(_)) => respond(StatusCode::NoContent, None),
+        Ok(Err(e)) if missing(&e) => error(StatusCode::NotFound, e),
+        Ok(Err(e)) => error(StatusCode::BadRequest, e),
+        Err(_) => stopped(),
+      }
+    }
+  }
+}
+
+/// Serves the admin API over the daemon's channel.
+/// Jobs and triggers are sent and returned in their typetag JSON form, such
+/// as `{"type": "SystemJob", ...}`. Requests wait while jobs are executing.
+///
+/// Anyone who can reach the API can schedule system jobs, which run any
+/// command as the scheduler's user, so every request must carry
+/// `Authorization: Bearer <token>`. Traffic isn't encrypted, so the listener
+/// should stay on a loopback address or behind a TLS proxy.
+pub async fn serve(listener: TcpListener, sender: Sender<Msg>, token: String) {
+  let mut incoming = listener.incoming();
+  while let Some(stream) = incoming.next().await {
+    let stream = match stream {
+      Ok(stream) => stream,
+      // Such as running out of file descriptors, only this connection is
+      // lost.
+      Err(_) => {
+        task::sleep(ACCEPT_BACKOFF).await;
+        continue;
+      }
+    };
+    let (sender, token) = (sender.clone(), token.clone());
+    task::spawn(async move {
+      let _ = async_h1::accept(stream, |req| {
+        let (sender, token) = (sender.clone(), token.clone());
+        async move { Ok(handle(req, sender, &token).await) }
+      })
+      .await;
+    });
+  }
+}
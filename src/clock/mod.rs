@@ -0,0 +1,79 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+#[typetag::serde(tag = "type")]
+pub trait Clock: Send + Sync {
+  /// Current time as nanoseconds since the unix epoch.
+  fn now(&self) -> i64;
+
+  fn vclone(&self) -> Box<dyn Clock>;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SystemClock;
+
+impl SystemClock {
+  pub fn new() -> Self {
+    SystemClock
+  }
+}
+
+#[typetag::serde]
+impl Clock for SystemClock {
+  fn now(&self) -> i64 {
+    Utc::now().timestamp_nanos()
+  }
+
+  fn vclone(&self) -> Box<dyn Clock> {
+    Box::new(self.clone())
+  }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep a handle and advance the clock it gave to the scheduler.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MockClock {
+  #[serde(serialize_with = "save_time", deserialize_with = "load_time")]
+  time: Arc<AtomicI64>,
+}
+
+impl MockClock {
+  pub fn new(time: i64) -> Self {
+    MockClock {
+      time: Arc::new(AtomicI64::new(time)),
+    }
+  }
+
+  pub fn set(&self, time: i64) {
+    self.time.store(time, Ordering::SeqCst);
+  }
+
+  pub fn advance(&self, by: i64) {
+    self.time.fetch_add(by, Ordering::SeqCst);
+  }
+}
+
+#[typetag::serde]
+impl Clock for MockClock {
+  fn now(&self) -> i64 {
+    self.time.load(Ordering::SeqCst)
+  }
+
+  fn vclone(&self) -> Box<dyn Clock> {
+    Box::new(self.clone())
+  }
+}
+
+fn save_time<S>(time: &Arc<AtomicI64>, s: S) -> Result<S::Ok, S::Error>
+where
+  S: Serializer, {
+  s.serialize_i64(time.load(Ordering::SeqCst))
+}
+
+fn load_time<'de, D>(d: D) -> Result<Arc<AtomicI64>, D::Error>
+where
+  D: Deserializer<'de>, {
+  Ok(Arc::new(AtomicI64::new(i64::deserialize(d)?)))
+}
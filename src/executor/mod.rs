@@ -1,44 +1,171 @@
-use crate::job::{Status, Work};
+use crate::job::{JobContext, Outcome, Status, Work};
+use crate::ledger::{memory, Ledger};
+use crate::trace::{self, Instrument};
+use async_channel::{Receiver, Sender};
+use async_std::task;
+use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
-use colored::*;
+use futures::{future, select, FutureExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Executor {
   pub alias: String,
+  // Nanoseconds a run may take when the job doesn't set its own timeout.
+  pub timeout: Option<i64>,
+}
+
+// Handed to a running job so it can stop early, and to the executor so it can
+// drop the job. Nothing is ever sent on the channel, cancelling closes it,
+// which wakes every clone at once.
+#[derive(Clone, Debug)]
+pub struct CancelToken {
+  sender: Sender<()>,
+  receiver: Receiver<()>,
+}
+
+impl CancelToken {
+  pub fn new() -> Self {
+    let (sender, receiver) = async_channel::bounded(1);
+    CancelToken { sender, receiver }
+  }
+
+  pub fn cancel(&self) {
+    self.sender.close();
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.receiver.is_closed()
+  }
+
+  pub async fn cancelled(&self) {
+    let _ = self.receiver.recv().await;
+  }
+}
+
+type Runs = HashMap<(String, String), CancelToken>;
+
+// Runs in flight, keyed by (store, job), so they can be cancelled from
+// outside the task executing them. Clones share the same runs.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellations {
+  runs: Arc<Mutex<Runs>>,
+}
+
+impl Cancellations {
+  pub fn new() -> Self {
+    Cancellations {
+      runs: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  pub fn register(&self, store: &String, job: &String) -> CancelToken {
+    let token = CancelToken::new();
+    self
+      .runs
+      .lock()
+      .unwrap()
+      .insert((store.clone(), job.clone()), token.clone());
+    token
+  }
+
+  pub fn finish(&self, store: &String, job: &String) {
+    self
+      .runs
+      .lock()
+      .unwrap()
+      .remove(&(store.clone(), job.clone()));
+  }
+
+  pub fn cancel(&self, store: &String, job: &String) -> Result<(), String> {
+    match self.runs.lock().unwrap().get(&(store.clone(), job.clone())) {
+      Some(token) => {
+        token.cancel();
+        Ok(())
+      }
+      None => Err(format!(
+        "Failed to cancel Job {}, it's not running in Store {}",
+        job, store
+      )),
+    }
+  }
 }
 
 impl Executor {
   pub fn new(alias: String) -> Executor {
-    Executor { alias }
+    Executor {
+      alias,
+      timeout: None,
+    }
   }
 
   pub fn startup(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Starting Executor ".truecolor(0,0,0).bold().on_green(),
-      self.alias.truecolor(0,0,0).bold().on_green(),
-      "   ::::".truecolor(0,0,0).bold().on_green()
-    );
     Ok(())
   }
 
+  // Runs a job outside of any scheduler, so it sees an empty ledger. The
+  // job is started up before and torn down after the run.
   pub async fn execute(&self, job: &Box<dyn Work>) -> Result<(), String> {
-    match job.func().await {
+    let ledger =
+      Ledger::new(self.alias.clone(), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx =
+      JobContext::new(&ledger, String::new(), String::new(), now, now, 1);
+    job.startup().await?;
+    let status = match job.before(&ctx).await {
+      Ok(_) => {
+        let span = trace::job(&String::new(), &String::new(), &self.alias);
+        let outcome = self
+          .run(job, &ctx, None)
+          .instrument(trace::run(&span, &String::new(), now))
+          .await;
+        match job.after(&ctx, &outcome).await {
+          Ok(_) => outcome.status,
+          Err(e) => Status::Failure(e),
+        }
+      }
+      Err(e) => Status::Failure(e),
+    };
+    job.teardown().await?;
+    match status {
       Status::Waiting => Ok(()),
       Status::Success => Ok(()),
       Status::Running => Ok(()),
       Status::Paused => Ok(()),
+      Status::Missed => Ok(()),
       Status::Failure(reason) => Err(reason),
     }
   }
 
+  // Runs the job until it finishes, times out or the context's token is
+  // cancelled. The job's future is dropped on timeout or cancellation, which
+  // kills sys jobs, and the failure keeps whatever progress it reported.
+  pub async fn run(
+    &self,
+    job: &Box<dyn Work>,
+    ctx: &JobContext<'_>,
+    timeout: Option<i64>,
+  ) -> Outcome {
+    trace::record_attempt(ctx.attempt);
+    let timer = match timeout.or(self.timeout) {
+      Some(t) => task::sleep(Duration::from_nanos(t.max(0) as u64)).boxed(),
+      None => future::pending().boxed(),
+    };
+
+    select! {
+      outcome = job.run(ctx).fuse() => outcome,
+      _ = timer.fuse() => {
+        ctx.progress.outcome(Status::Failure(String::from("timeout")))
+      }
+      _ = ctx.cancel.cancelled().fuse() => {
+        ctx.progress.outcome(Status::Failure(String::from("cancelled")))
+      }
+    }
+  }
+
   pub fn teardown(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Tearing Down Executor ".truecolor(0,0,0).bold().on_green(),
-      self.alias.truecolor(0,0,0).bold().on_green(),
-      "   ::::".truecolor(0,0,0).bold().on_green()
-    );
     Ok(())
   }
 }
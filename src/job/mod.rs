@@ -1,14 +1,18 @@
 pub mod network;
 pub mod sys;
+pub mod workflow;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
 
+use crate::executor::CancelToken;
 use crate::ledger::Ledger;
-use crate::trigger::Trigger;
+use crate::logger::Logger;
+use crate::trigger::{Trigger, TriggerContext};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub enum Status {
@@ -17,6 +21,7 @@ pub enum Status {
   Paused,
   Success,
   Failure(String),
+  Missed,
 }
 
 impl Status {
@@ -27,17 +32,206 @@ impl Status {
       Status::Paused => String::from("Paused"),
       Status::Success => String::from("Success"),
       Status::Failure(_) => String::from("Failure"),
+      Status::Missed => String::from("Missed"),
     }
   }
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum MisfirePolicy {
+  // Run once for all the missed fire times.
+  FireOnce,
+  // Run once for every missed fire time.
+  FireAll,
+  // Drop the missed fire times and wait for the next one.
+  Skip,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Misfire {
+  pub grace: i64,
+  pub policy: MisfirePolicy,
+}
+
+impl Misfire {
+  pub fn new(grace: i64, policy: MisfirePolicy) -> Self {
+    Misfire { grace, policy }
+  }
+}
+
+// The result of a single run, with whatever the job captured along the way
+// (exit codes, output, ...) so it can be kept in the ledger. `output` is the
+// payload handed to downstream jobs, `steps` holds the outcomes of the nodes
+// a workflow ran.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Outcome {
+  pub status: Status,
+  pub captures: BTreeMap<String, String>,
+  pub output: Option<String>,
+  pub steps: Vec<(String, Outcome)>,
+}
+
+impl Outcome {
+  pub fn new(status: Status) -> Self {
+    Outcome {
+      status,
+      captures: BTreeMap::new(),
+      output: None,
+      steps: vec![],
+    }
+  }
+
+  pub fn with_captures(
+    status: Status,
+    captures: BTreeMap<String, String>,
+  ) -> Self {
+    Outcome {
+      status,
+      captures,
+      output: None,
+      steps: vec![],
+    }
+  }
+
+  pub fn with_output(status: Status, output: String) -> Self {
+    Outcome {
+      status,
+      captures: BTreeMap::new(),
+      output: Some(output),
+      steps: vec![],
+    }
+  }
+}
+
+/// What a run managed to do so far, shared between a job and its executor so
+/// a run that times out or is cancelled keeps it. Workflows report their run
+/// id and every step as soon as it finishes.
+// Captures and steps reported so far.
+type Partial = (BTreeMap<String, String>, Vec<(String, Outcome)>);
+
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+  partial: Arc<Mutex<Partial>>,
+}
+
+impl Progress {
+  pub fn new() -> Self {
+    Progress::default()
+  }
+
+  pub fn capture(&self, key: String, value: String) {
+    self.partial.lock().unwrap().0.insert(key, value);
+  }
+
+  pub fn step(&self, node: String, outcome: Outcome) {
+    self.partial.lock().unwrap().1.push((node, outcome));
+  }
+
+  // An outcome with the given status and everything reported so far.
+  pub fn outcome(&self, status: Status) -> Outcome {
+    let (captures, steps) = self.partial.lock().unwrap().clone();
+    let mut outcome = Outcome::with_captures(status, captures);
+    outcome.steps = steps;
+    outcome
+  }
+}
+
+/// What a job can see while it runs. The scheduler builds one per run.
+/// Times are in nanoseconds, `attempt` starts at 1 and grows while the job
+/// keeps failing. `inputs` holds the outputs of a workflow node's parents,
+/// `resume` the id of a workflow run being retried, and `progress` what the
+/// run reported before it finished.
+pub struct JobContext<'a> {
+  pub ledger: &'a Ledger,
+  pub store: String,
+  pub job: String,
+  pub scheduled_time: i64,
+  pub start: i64,
+  pub attempt: u32,
+  pub cancel: CancelToken,
+  pub logger: Option<Logger>,
+  pub inputs: HashMap<String, String>,
+  pub resume: Option<String>,
+  pub progress: Progress,
+}
+
+impl<'a> JobContext<'a> {
+  pub fn new(
+    ledger: &'a Ledger,
+    store: String,
+    job: String,
+    scheduled_time: i64,
+    start: i64,
+    attempt: u32,
+  ) -> Self {
+    JobContext {
+      ledger,
+      store,
+      job,
+      scheduled_time,
+      start,
+      attempt,
+      cancel: CancelToken::new(),
+      logger: None,
+      inputs: HashMap::new(),
+      resume: None,
+      progress: Progress::new(),
+    }
+  }
+
+  /// Latest output recorded for a job, usually one this job depends on.
+  pub fn output(&self, store: &String, job: &String) -> Option<String> {
+    self.ledger.ledger.output(store, job)
+  }
+}
+
+// Default number of bytes of captured output kept per run.
+pub const OUTPUT_LIMIT: usize = 4096;
+
+// Lossily decodes captured output, keeping at most `limit` bytes.
+pub fn truncate_output(bytes: &[u8], limit: usize) -> String {
+  let mut output = String::from_utf8_lossy(bytes).into_owned();
+  if output.len() > limit {
+    let mut end = limit;
+    while !output.is_char_boundary(end) {
+      end -= 1;
+    }
+    output.truncate(end);
+  }
+  output
+}
+
 #[async_trait]
 #[typetag::serde(tag = "type")]
 pub trait Work: Send + Sync {
+  // Called once when the job is added to a scheduler with a running executor,
+  // or when its executor starts. The job doesn't run until it succeeds.
   async fn startup(&self) -> Result<(), String>;
 
-  async fn func(&self) -> Status;
+  // Called before every run, an error skips the run.
+  async fn before(&self, _ctx: &JobContext<'_>) -> Result<(), String> {
+    Ok(())
+  }
+
+  async fn func(&self, ctx: &JobContext<'_>) -> Status;
+
+  // Jobs that capture anything or hand an output downstream override this,
+  // func alone only reports a status.
+  async fn run(&self, ctx: &JobContext<'_>) -> Outcome {
+    Outcome::new(self.func(ctx).await)
+  }
+
+  // Called after every run that wasn't skipped, an error fails the run.
+  async fn after(
+    &self,
+    _ctx: &JobContext<'_>,
+    _outcome: &Outcome,
+  ) -> Result<(), String> {
+    Ok(())
+  }
 
+  // Called once when the job is removed, its executor is removed or the
+  // scheduler shuts down.
   async fn teardown(&self) -> Result<(), String>;
 
   fn vclone(&self) -> Box<dyn Work>;
@@ -51,6 +245,17 @@ pub struct Job {
   pub start_time: i64,
   pub end_time: Option<i64>,
   pub triggers: HashMap<String, Box<Trigger>>,
+  pub last_run: Option<i64>,
+  pub last_status: Option<Status>,
+  pub misfire: Option<Misfire>,
+  // Nanoseconds a run may take, overrides the executor's default.
+  pub timeout: Option<i64>,
+  // Runs in a row, counting the latest one, that ended in a failure plus one.
+  pub attempt: u32,
+  // Whether Work::startup succeeded and teardown hasn't been called since,
+  // jobs are started again after a snapshot is loaded.
+  #[serde(skip)]
+  pub started: bool,
   pub job: Box<dyn Work>,
 }
 
@@ -70,49 +275,52 @@ impl Job {
       start_time,
       end_time,
       triggers,
+      last_run: None,
+      last_status: None,
+      misfire: None,
+      timeout: None,
+      attempt: 1,
+      started: false,
       job,
     }
   }
 
+  pub async fn startup(&mut self) -> Result<(), String> {
+    if !self.started {
+      self.job.startup().await?;
+      self.started = true;
+    }
+    Ok(())
+  }
+
+  pub async fn teardown(&mut self) -> Result<(), String> {
+    if self.started {
+      self.job.teardown().await?;
+      self.started = false;
+    }
+    Ok(())
+  }
+
   pub async fn validate_triggers(
     &mut self,
-    ledger: &mut Ledger,
+    ctx: &TriggerContext<'_>,
   ) -> (bool, Option<i64>) {
     let mut should_run = true;
     let mut next = None;
     for (_, value) in &mut self.triggers {
-      let trig = &mut value.trigger;
-      if trig.needs_ledger() {
-        if !(trig.should_run_with_ledger(ledger).await) {
-          should_run = false;
-        } else {
-          match trig.next().await {
-            Some(v) => match next {
-              Some(curr) => {
-                if v < curr {
-                  next = Some(v);
-                }
-              }
-              None => next = Some(v),
-            },
-            None => (),
-          }
-        }
+      if !(value.trigger.evaluate(ctx).await) {
+        should_run = false;
       } else {
-        if !(trig.should_run().await) {
-          should_run = false;
-        } else {
-          match trig.next().await {
-            Some(v) => match next {
-              Some(curr) => {
-                if v < curr {
-                  next = Some(v);
-                }
+        match value.trigger.next(ctx).await {
+          Some(v) => match next {
+            Some(curr) => {
+              if v < curr {
+                next = Some(v);
               }
-              None => next = Some(v),
-            },
-            None => (),
-          }
+            }
+            None => next = Some(v),
+          },
+          None => (),
         }
       }
     }
@@ -120,8 +328,20 @@ impl Job {
     (should_run, next)
   }
 
-  // TODO: Implement Modify Job
-  pub fn modify_job(&mut self) -> Result<(), String> {
+  // Swaps in new work and schedule, the triggers, misfire policy, timeout
+  // and run history are kept. The caller tears the old work down first.
+  pub fn modify_job(
+    &mut self,
+    executor: String,
+    start_time: i64,
+    end_time: Option<i64>,
+    job: Box<dyn Work>,
+  ) -> Result<(), String> {
+    self.executor = executor;
+    self.start_time = start_time;
+    self.end_time = end_time;
+    self.job = job;
+    self.started = false;
     Ok(())
   }
 
@@ -137,6 +357,19 @@ impl Job {
     Ok(())
   }
 
+  pub fn set_misfire(
+    &mut self,
+    misfire: Option<Misfire>,
+  ) -> Result<(), String> {
+    self.misfire = misfire;
+    Ok(())
+  }
+
+  pub fn set_timeout(&mut self, timeout: Option<i64>) -> Result<(), String> {
+    self.timeout = timeout;
+    Ok(())
+  }
+
   pub fn add_trigger(&mut self, trigger: Trigger) -> Result<(), String> {
     match self.triggers.entry(trigger.alias.clone()) {
       Entry::Occupied(_) => {
@@ -149,6 +382,18 @@ impl Job {
     }
   }
 
+  pub fn modify_trigger(&mut self, trigger: Trigger) -> Result<(), String> {
+    match self.triggers.entry(trigger.alias.clone()) {
+      Entry::Occupied(mut e) => {
+        e.insert(Box::new(trigger));
+        Ok(())
+      }
+      Entry::Vacant(_) => {
+        Err(format!("Trigger {} doesn't exists", trigger.alias.clone()))
+      }
+    }
+  }
+
   pub fn remove_trigger(
     &mut self,
     trigger_alias: String,
@@ -174,6 +419,12 @@ impl Clone for Job {
       start_time: self.start_time,
       end_time: self.end_time,
       triggers: self.triggers.clone(),
+      last_run: self.last_run,
+      last_status: self.last_status.clone(),
+      misfire: self.misfire.clone(),
+      timeout: self.timeout,
+      attempt: self.attempt,
+      started: self.started,
       job: self.job.vclone(),
     }
   }
@@ -188,6 +439,12 @@ impl Debug for Job {
       .field("start_time", &self.start_time)
       .field("end_success", &self.end_time)
       .field("triggers", &self.triggers)
+      .field("last_run", &self.last_run)
+      .field("last_status", &self.last_status)
+      .field("misfire", &self.misfire)
+      .field("timeout", &self.timeout)
+      .field("attempt", &self.attempt)
+      .field("started", &self.started)
       .field("job", &"<job>")
       .finish()
   }
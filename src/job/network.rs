@@ -1,22 +1,229 @@
 use async_trait::async_trait;
-use colored::*;
+use http_client::isahc::IsahcClient;
+use http_types::auth::BasicAuth;
+use http_types::headers::{HeaderName, HeaderValue};
+use http_types::{mime, Method, Url};
+use isahc::config::{
+  CaCertificate, ClientCertificate, Configurable, PrivateKey,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::time::Instant;
 
-use crate::job::{Status, Work};
+use crate::job::{
+  truncate_output, JobContext, Outcome, Status, Work, OUTPUT_LIMIT,
+};
+use crate::secret::Resolve;
+use crate::trace::{self, Instrument};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum NetType {
   Get,
   Post,
+  Put,
+  Patch,
+  Delete,
+  Head,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl NetType {
+  fn method(&self) -> Method {
+    match self {
+      NetType::Get => Method::Get,
+      NetType::Post => Method::Post,
+      NetType::Put => Method::Put,
+      NetType::Patch => Method::Patch,
+      NetType::Delete => Method::Delete,
+      NetType::Head => Method::Head,
+    }
+  }
+}
+
+// Json holds the document as text so the job stays snapshot friendly, it is
+// checked to be valid JSON before the request is sent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Body {
+  Raw(String),
+  Json(String),
+  Form(Vec<(String, String)>),
+}
+
+impl Body {
+  fn encode(&self) -> Result<http_types::Body, String> {
+    match self {
+      Body::Raw(raw) => Ok(http_types::Body::from_string(raw.clone())),
+      Body::Json(json) => {
+        match serde_json::from_str::<serde_json::Value>(json) {
+          Ok(value) => http_types::Body::from_json(&value)
+            .map_err(|_e| String::from("Unable to parse body")),
+          Err(_e) => Err(String::from("Unable to parse body")),
+        }
+      }
+      Body::Form(form) => match serde_urlencoded::to_string(form) {
+        Ok(encoded) => {
+          let mut body = http_types::Body::from_string(encoded);
+          body.set_mime(mime::FORM);
+          Ok(body)
+        }
+        Err(_e) => Err(String::from("Unable to parse body")),
+      },
+    }
+  }
+}
+
+// Checks made against a response once its status is within `success`.
+// JsonPointer compares the value at the pointer with the expected JSON text,
+// text that isn't valid JSON is compared as a JSON string.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Assertion {
+  Status(Vec<u16>),
+  BodyContains(String),
+  JsonPointer(String, String),
+  Header(String),
+  // Nanoseconds
+  MaxLatency(i64),
+}
+
+impl Assertion {
+  fn check(
+    &self,
+    status: u16,
+    latency: i64,
+    headers: &HashMap<String, String>,
+    body: &String,
+  ) -> Result<(), String> {
+    match self {
+      Assertion::Status(expected) => {
+        if expected.contains(&status) {
+          Ok(())
+        } else {
+          Err(format!("Expected status in {:?}, got {}", expected, status))
+        }
+      }
+      Assertion::BodyContains(expected) => {
+        if body.contains(expected.as_str()) {
+          Ok(())
+        } else {
+          Err(format!("Expected body to contain {:?}", expected))
+        }
+      }
+      Assertion::JsonPointer(pointer, expected) => {
+        let document: Value = match serde_json::from_str(body) {
+          Ok(document) => document,
+          Err(_e) => return Err(String::from("Expected a JSON body")),
+        };
+        let expected = serde_json::from_str(expected)
+          .unwrap_or_else(|_e| Value::String(expected.clone()));
+        match document.pointer(pointer) {
+          Some(actual) if *actual == expected => Ok(()),
+          Some(actual) => Err(format!(
+            "Expected {} to equal {}, got {}",
+            pointer, expected, actual
+          )),
+          None => Err(format!("Expected {} to equal {}", pointer, expected)),
+        }
+      }
+      Assertion::Header(name) => {
+        if headers.contains_key(&name.to_lowercase()) {
+          Ok(())
+        } else {
+          Err(format!("Expected header {}", name))
+        }
+      }
+      Assertion::MaxLatency(max) => {
+        if latency <= *max {
+          Ok(())
+        } else {
+          Err(format!(
+            "Expected a response within {}ns, took {}ns",
+            max, latency
+          ))
+        }
+      }
+    }
+  }
+}
+
+// Secrets are referenced by name and resolved through the job's secrets
+// provider on every run.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Auth {
+  // Secret name of the token
+  Bearer(String),
+  // Username, secret name of the password
+  Basic(String, String),
+}
+
+// Certificates are PEM files, only the key password is a secret.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Tls {
+  pub ca: Option<String>,
+  pub cert: Option<String>,
+  pub key: Option<String>,
+  pub key_password: Option<String>,
+}
+
+impl Tls {
+  pub fn new() -> Self {
+    Tls {
+      ca: None,
+      cert: None,
+      key: None,
+      key_password: None,
+    }
+  }
+}
+
+fn default_success() -> Vec<(u16, u16)> {
+  vec![(100, 399)]
+}
+
+fn default_output_limit() -> usize {
+  OUTPUT_LIMIT
+}
+
+// Fields missing from older or shorter payloads take `Job::new`'s defaults.
+#[derive(Serialize, Deserialize)]
 pub struct Job {
   pub alias: String,
   pub url: String,
   pub method: NetType,
-  pub headers: Option<String>,
-  pub body: Option<String>,
+  #[serde(default)]
+  pub headers: HashMap<String, String>,
+  #[serde(default)]
+  pub body: Option<Body>,
+  #[serde(default)]
+  pub query: Vec<(String, String)>,
+  // Inclusive ranges of response statuses counted as a success.
+  #[serde(default = "default_success")]
+  pub success: Vec<(u16, u16)>,
+  #[serde(default)]
+  pub assertions: Vec<Assertion>,
+  #[serde(default = "default_output_limit")]
+  pub output_limit: usize,
+  #[serde(default)]
+  pub auth: Option<Auth>,
+  #[serde(default)]
+  pub tls: Option<Tls>,
+  #[serde(default)]
+  pub secrets: Option<Box<dyn Resolve>>,
+}
+
+// Adds a header, failing instead of panicking like `header` does on names or
+// values that aren't ASCII.
+fn header(
+  request: surf::RequestBuilder,
+  name: &str,
+  value: &str,
+) -> Result<surf::RequestBuilder, String> {
+  let name = HeaderName::from_string(name.to_string())
+    .map_err(|_e| format!("Invalid header name {:?}", name))?;
+  let value = value
+    .parse::<HeaderValue>()
+    .map_err(|_e| format!("Invalid value for header {}", name))?;
+  Ok(request.header(name, value))
 }
 
 impl Job {
@@ -24,8 +231,8 @@ impl Job {
     alias: String,
     url: String,
     method: NetType,
-    headers: Option<String>,
-    body: Option<String>,
+    headers: HashMap<String, String>,
+    body: Option<Body>,
   ) -> Self {
     Job {
       alias,
@@ -33,86 +240,162 @@ impl Job {
       method,
       headers,
       body,
+      query: vec![],
+      success: default_success(),
+      assertions: vec![],
+      output_limit: default_output_limit(),
+      auth: None,
+      tls: None,
+      secrets: None,
     }
   }
+
+  fn secret(&self, name: &String) -> Result<String, String> {
+    match &self.secrets {
+      Some(secrets) => secrets.resolve(name),
+      None => Err(format!(
+        "Secret {} can't be resolved, Job {} has no secrets provider",
+        name, &self.alias
+      )),
+    }
+  }
+
+  fn client(&self) -> Result<surf::Client, String> {
+    let tls = match &self.tls {
+      Some(tls) => tls,
+      None => return Ok(surf::Client::new()),
+    };
+
+    let mut builder = isahc::HttpClient::builder();
+    if let Some(ca) = &tls.ca {
+      builder = builder.ssl_ca_certificate(CaCertificate::file(ca));
+    }
+    if let Some(cert) = &tls.cert {
+      let password = match &tls.key_password {
+        Some(name) => Some(self.secret(name)?),
+        None => None,
+      };
+      let key = tls
+        .key
+        .as_ref()
+        .map(|key| PrivateKey::pem_file(key, password));
+      builder =
+        builder.ssl_client_certificate(ClientCertificate::pem_file(cert, key));
+    }
+    match builder.build() {
+      Ok(client) => Ok(surf::Client::with_http_client(
+        IsahcClient::from_client(client),
+      )),
+      Err(e) => Err(format!("Unable to configure TLS: {}", e)),
+    }
+  }
+
+  fn request(&self) -> Result<surf::RequestBuilder, String> {
+    let mut url = match Url::parse(&self.url) {
+      Ok(url) => url,
+      Err(_e) => return Err(format!("Unable to parse url {}", &self.url)),
+    };
+    if !self.query.is_empty() {
+      url.query_pairs_mut().extend_pairs(&self.query);
+    }
+
+    let mut request = surf::RequestBuilder::new(self.method.method(), url);
+    for (name, value) in &self.headers {
+      request = header(request, name, value)?;
+    }
+    match &self.auth {
+      Some(Auth::Bearer(token)) => {
+        let token = self.secret(token)?;
+        let value = format!("Bearer {}", token);
+        request = header(request, "Authorization", &value)?;
+      }
+      Some(Auth::Basic(username, password)) => {
+        let auth = BasicAuth::new(username, self.secret(password)?);
+        let (name, value) = (auth.name(), auth.value());
+        request = header(request, name.as_str(), value.as_str())?;
+      }
+      None => (),
+    }
+    match &self.body {
+      Some(body) => Ok(request.body(body.encode()?)),
+      None => Ok(request),
+    }
+  }
+
+  pub async fn outcome(&self) -> Outcome {
+    let (client, request) = match self.client().and_then(|client| {
+      self.request().map(|request| (client, request.build()))
+    }) {
+      Ok(prepared) => prepared,
+      Err(e) => return Outcome::new(Status::Failure(e)),
+    };
+    let started = Instant::now();
+    let span = trace::request(self.method.method().to_string(), &self.url);
+    let mut res = match client.send(request).instrument(span).await {
+      Ok(res) => res,
+      Err(e) => {
+        return Outcome::new(Status::Failure(format!(
+          "Unable to complete request: {}",
+          e
+        )))
+      }
+    };
+    let bytes = res.body_bytes().await.unwrap_or_default();
+    let latency = started.elapsed().as_nanos() as i64;
+    let status: u16 = res.status().into();
+    let body = String::from_utf8_lossy(&bytes).into_owned();
+    let headers: HashMap<String, String> = res
+      .iter()
+      .map(|(name, values)| {
+        (
+          name.as_str().to_lowercase(),
+          values.last().as_str().to_string(),
+        )
+      })
+      .collect();
+
+    let mut captures = BTreeMap::new();
+    captures.insert(String::from("status"), status.to_string());
+    captures.insert(String::from("latency"), latency.to_string());
+    captures.insert(
+      String::from("body"),
+      truncate_output(&bytes, self.output_limit),
+    );
+
+    let status = if !self
+      .success
+      .iter()
+      .any(|(low, high)| *low <= status && status <= *high)
+    {
+      Status::Failure(format!("Request failed with status {}", status))
+    } else {
+      match self.assertions.iter().find_map(|assertion| {
+        assertion.check(status, latency, &headers, &body).err()
+      }) {
+        Some(e) => Status::Failure(e),
+        None => Status::Success,
+      }
+    };
+    Outcome::with_captures(status, captures)
+  }
 }
 
 #[async_trait]
 #[typetag::serde(name = "NetworkJob")]
 impl Work for Job {
   async fn startup(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Starting Network Job "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
-  async fn func(&self) -> Status {
-    match &self.method {
-      NetType::Get => match surf::get(&self.url).await {
-        Ok(msg) => {
-          println!("Network Job Response Status: {}", msg.status());
-          if (msg.status().is_server_error() || msg.status().is_client_error())
-          {
-            return Status::Failure(String::from("Unable to complete request"));
-          } else {
-            return Status::Success;
-          }
-        }
-        Err(_) => Status::Failure(String::from("Unable to complete request")),
-      },
-      NetType::Post => match &self.body {
-        Some(bdy) => {
-          let data = serde_json::json!(bdy);
-          match http_types::Body::from_json(&data) {
-            Ok(bdy) => {
-              let res = surf::post(&self.url).body(bdy).await;
-
-              match res {
-                Ok(_r) => {
-                  // println!("Network Job Response Status: {}", r.status());
-                  Status::Success
-                }
-                Err(_) => {
-                  Status::Failure(String::from("Unable to complete request"))
-                }
-              }
-            }
-            Err(_) => Status::Failure(String::from("Unable to parse body")),
-          }
-        }
-        None => {
-          let res = surf::post(&self.url).await;
-          match res {
-            Ok(_r) => {
-              // println!("Network Job Response Status: {}", r.status());
-              Status::Success
-            }
-            Err(_) => {
-              Status::Failure(String::from("Unable to complete request"))
-            }
-          }
-        }
-      },
-    }
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    self.outcome().await.status
+  }
+
+  async fn run(&self, _ctx: &JobContext<'_>) -> Outcome {
+    self.outcome().await
   }
 
   async fn teardown(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Tearing Down Network Job "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
@@ -120,3 +403,41 @@ impl Work for Job {
     Box::new(self.clone())
   }
 }
+
+impl Clone for Job {
+  fn clone(&self) -> Self {
+    Job {
+      alias: self.alias.clone(),
+      url: self.url.clone(),
+      method: self.method.clone(),
+      headers: self.headers.clone(),
+      body: self.body.clone(),
+      query: self.query.clone(),
+      success: self.success.clone(),
+      assertions: self.assertions.clone(),
+      output_limit: self.output_limit,
+      auth: self.auth.clone(),
+      tls: self.tls.clone(),
+      secrets: self.secrets.as_ref().map(|secrets| secrets.vclone()),
+    }
+  }
+}
+
+impl Debug for Job {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Job")
+      .field("alias", &self.alias)
+      .field("url", &self.url)
+      .field("method", &self.method)
+      .field("headers", &self.headers)
+      .field("body", &self.body)
+      .field("query", &self.query)
+      .field("success", &self.success)
+      .field("assertions", &self.assertions)
+      .field("output_limit", &self.output_limit)
+      .field("auth", &self.auth)
+      .field("tls", &self.tls)
+      .field("secrets", &"<secrets>")
+      .finish()
+  }
+}
@@ -1,15 +1,76 @@
-use async_process::Command;
+use async_process::{Command, Output, Stdio};
 use async_trait::async_trait;
-use colored::*;
+use futures::future;
+use futures::io::{self as aio, AsyncRead, AsyncReadExt, AsyncWriteExt};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
 
-use crate::job::{Status, Work};
+use crate::job::{
+  truncate_output, JobContext, Outcome, Status, Work, OUTPUT_LIMIT,
+};
 
+// Reads up to `limit` bytes from a pipe of the child and drains the rest.
+async fn read_limited<R: AsyncRead + Unpin>(
+  pipe: Option<R>,
+  limit: usize,
+) -> io::Result<Vec<u8>> {
+  let mut bytes = vec![];
+  if let Some(mut pipe) = pipe {
+    (&mut pipe).take(limit as u64).read_to_end(&mut bytes).await?;
+    aio::copy(pipe, &mut aio::sink()).await?;
+  }
+  Ok(bytes)
+}
+
+// Whether the child starts from the scheduler's environment or an empty one,
+// `env` is applied on top in both cases.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum EnvMode {
+  Inherit,
+  Clear,
+}
+
+impl Default for EnvMode {
+  fn default() -> Self {
+    EnvMode::Inherit
+  }
+}
+
+fn default_success_codes() -> Vec<i32> {
+  vec![0]
+}
+
+fn default_output_limit() -> usize {
+  OUTPUT_LIMIT
+}
+
+// Fields missing from older or shorter payloads take `Job::new`'s defaults.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Job {
   pub alias: String,
   pub script: String,
+  #[serde(default)]
   pub args: Vec<String>,
+  #[serde(default = "default_success_codes")]
+  pub success_codes: Vec<i32>,
+  #[serde(default = "default_output_limit")]
+  pub output_limit: usize,
+  #[serde(default)]
+  pub env_mode: EnvMode,
+  #[serde(default)]
+  pub env: BTreeMap<String, String>,
+  #[serde(default)]
+  pub cwd: Option<String>,
+  #[serde(default)]
+  pub stdin: Option<String>,
+  // Runs `script` through `sh -c`, `args` become its positional parameters.
+  #[serde(default)]
+  pub shell: bool,
+  #[serde(default)]
+  pub uid: Option<u32>,
+  #[serde(default)]
+  pub gid: Option<u32>,
 }
 
 impl Job {
@@ -18,6 +79,136 @@ impl Job {
       alias,
       script,
       args,
+      success_codes: default_success_codes(),
+      output_limit: default_output_limit(),
+      env_mode: EnvMode::default(),
+      env: BTreeMap::new(),
+      cwd: None,
+      stdin: None,
+      shell: false,
+      uid: None,
+      gid: None,
+    }
+  }
+
+  fn command(&self) -> io::Result<Command> {
+    let mut command = if self.shell {
+      let mut command = Command::new("sh");
+      command.arg("-c").arg(&self.script).arg("sh");
+      command
+    } else {
+      Command::new(&self.script)
+    };
+    command.args(&self.args).kill_on_drop(true);
+
+    if self.env_mode == EnvMode::Clear {
+      command.env_clear();
+    }
+    command.envs(&self.env);
+
+    if let Some(cwd) = &self.cwd {
+      command.current_dir(cwd);
+    }
+
+    #[cfg(unix)]
+    {
+      use async_process::unix::CommandExt;
+      if let Some(uid) = self.uid {
+        command.uid(uid);
+      }
+      if let Some(gid) = self.gid {
+        command.gid(gid);
+      }
+    }
+    #[cfg(not(unix))]
+    {
+      if self.uid.is_some() || self.gid.is_some() {
+        return Err(io::Error::new(
+          io::ErrorKind::Other,
+          "uid/gid switching is only supported on unix",
+        ));
+      }
+    }
+
+    Ok(command)
+  }
+
+  // Writes stdin while stdout and stderr are read, so a child filling its
+  // pipes before reading its input doesn't block either side. Only the first
+  // `output_limit` bytes of each are kept, the rest is drained so the child
+  // can't stall on a full pipe.
+  async fn output(&self) -> io::Result<Output> {
+    let mut command = self.command()?;
+    let stdin = match &self.stdin {
+      Some(_) => Stdio::piped(),
+      None => Stdio::null(),
+    };
+    let mut child = command
+      .stdin(stdin)
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()?;
+    // Dropping stdin once it's written closes it.
+    let (stdin, input) = (child.stdin.take(), self.stdin.as_deref());
+    let write = async move {
+      match (stdin, input) {
+        (Some(mut stdin), Some(input)) => {
+          stdin.write_all(input.as_bytes()).await
+        }
+        _ => Ok(()),
+      }
+    };
+    let stdout = read_limited(child.stdout.take(), self.output_limit);
+    let stderr = read_limited(child.stderr.take(), self.output_limit);
+    let (written, stdout, stderr) =
+      future::join3(write, stdout, stderr).await;
+    let status = child.status().await?;
+    match written {
+      // The child may exit without reading all of its input.
+      Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+      _ => Ok(Output {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+      }),
+    }
+  }
+
+  pub async fn outcome(&self) -> Outcome {
+    match self.output().await {
+      Ok(output) => {
+        let mut captures = BTreeMap::new();
+        captures.insert(
+          String::from("stdout"),
+          truncate_output(&output.stdout, self.output_limit),
+        );
+        captures.insert(
+          String::from("stderr"),
+          truncate_output(&output.stderr, self.output_limit),
+        );
+        let status = match output.status.code() {
+          Some(code) => {
+            captures.insert(String::from("exit_code"), code.to_string());
+            if self.success_codes.contains(&code) {
+              Status::Success
+            } else {
+              Status::Failure(format!(
+                "Failed to successfully run {} with {:?}, exit code {}",
+                &self.script, &self.args, code
+              ))
+            }
+          }
+          None => Status::Failure(format!(
+            "Failed to successfully run {} with {:?}, terminated by signal",
+            &self.script, &self.args
+          )),
+        };
+        Outcome::with_captures(status, captures)
+      }
+      Err(e) => Outcome::new(Status::Failure(format!(
+        "Failed to successfully run {} with {:?}: {}",
+        &self.script, &self.args, e
+      ))),
     }
   }
 }
@@ -26,42 +217,18 @@ impl Job {
 #[typetag::serde(name = "SystemJob")]
 impl Work for Job {
   async fn startup(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Starting Sys Job "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
-  async fn func(&self) -> Status {
-    match Command::new(&self.script.clone())
-      .args(&self.args)
-      .output()
-      .await
-    {
-      Ok(_) => Status::Success,
-      Err(_e) => Status::Failure(format!(
-        "Failed to successfully run {} with {:?}",
-        &self.script, &self.args
-      )),
-    }
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    self.outcome().await.status
+  }
+
+  async fn run(&self, _ctx: &JobContext<'_>) -> Outcome {
+    self.outcome().await
   }
 
   async fn teardown(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Tearing Down Sys Job "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
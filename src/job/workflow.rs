@@ -0,0 +1,337 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::job::{JobContext, Outcome, Status, Work};
+use crate::ledger::Ledger;
+
+// Capture tying every node outcome to the workflow run it belongs to.
+pub const RUN_ID: &str = "workflow_run";
+
+// Ledger alias under which a node of a workflow job records its outcomes.
+pub fn step_alias(job: &String, node: &String) -> String {
+  format!("{}/{}", job, node)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Workflow {
+  pub alias: String,
+  pub nodes: HashMap<String, Box<dyn Work>>,
+  // (parent, child)
+  pub edges: Vec<(String, String)>,
+}
+
+impl Workflow {
+  pub fn new(alias: String) -> Self {
+    Workflow {
+      alias,
+      nodes: HashMap::new(),
+      edges: vec![],
+    }
+  }
+
+  pub fn add_node(
+    &mut self,
+    alias: String,
+    work: Box<dyn Work>,
+  ) -> Result<(), String> {
+    match self.nodes.entry(alias.clone()) {
+      Entry::Occupied(_) => Err(format!(
+        "Node {} already exists in Workflow {}",
+        alias, &self.alias
+      )),
+      Entry::Vacant(entry) => {
+        entry.insert(work);
+        Ok(())
+      }
+    }
+  }
+
+  pub fn add_edge(
+    &mut self,
+    parent: String,
+    child: String,
+  ) -> Result<(), String> {
+    for node in &[&parent, &child] {
+      if !self.nodes.contains_key(*node) {
+        return Err(format!(
+          "Node {} doesn't exist in Workflow {}",
+          node, &self.alias
+        ));
+      }
+    }
+    self.edges.push((parent, child));
+    match self.validate() {
+      Ok(_) => Ok(()),
+      Err(e) => {
+        self.edges.pop();
+        Err(e)
+      }
+    }
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    let mut incoming: HashMap<&String, usize> =
+      self.nodes.keys().map(|node| (node, 0)).collect();
+    for (parent, child) in &self.edges {
+      if !self.nodes.contains_key(parent) {
+        return Err(format!(
+          "Node {} doesn't exist in Workflow {}",
+          parent, &self.alias
+        ));
+      }
+      match incoming.get_mut(child) {
+        Some(count) => *count += 1,
+        None => {
+          return Err(format!(
+            "Node {} doesn't exist in Workflow {}",
+            child, &self.alias
+          ))
+        }
+      }
+    }
+
+    let mut ready: Vec<&String> = incoming
+      .iter()
+      .filter(|(_, count)| **count == 0)
+      .map(|(node, _)| *node)
+      .collect();
+    let mut visited = 0;
+    while let Some(node) = ready.pop() {
+      visited += 1;
+      for (parent, child) in &self.edges {
+        if parent == node {
+          let count = incoming.get_mut(child).unwrap();
+          *count -= 1;
+          if *count == 0 {
+            ready.push(child);
+          }
+        }
+      }
+    }
+
+    if visited == self.nodes.len() {
+      Ok(())
+    } else {
+      Err(format!("Workflow {} has a cycle", &self.alias))
+    }
+  }
+
+  fn parents(&self, node: &String) -> Vec<&String> {
+    self
+      .edges
+      .iter()
+      .filter(|(_, child)| child == node)
+      .map(|(parent, _)| parent)
+      .collect()
+  }
+
+  /// Latest outcome of every node that ran as part of `run_id`.
+  pub fn run_outcomes(
+    &self,
+    ledger: &Ledger,
+    store: &String,
+    job: &String,
+    run_id: &String,
+  ) -> HashMap<String, Outcome> {
+    let mut outcomes = HashMap::new();
+    for node in self.nodes.keys() {
+      let latest = ledger
+        .ledger
+        .outcomes(store, &step_alias(job, node))
+        .into_iter()
+        .filter(|(_, outcome)| outcome.captures.get(RUN_ID) == Some(run_id))
+        .last();
+      if let Some((_, outcome)) = latest {
+        outcomes.insert(node.clone(), outcome);
+      }
+    }
+    outcomes
+  }
+
+  // Runs a node between its own before and after hooks, failing it as the
+  // scheduler fails a job when one of them does.
+  async fn run_node(&self, node: &String, ctx: &JobContext<'_>) -> Outcome {
+    let work = &self.nodes[node];
+    match work.before(ctx).await {
+      Ok(_) => {
+        let mut outcome = work.run(ctx).await;
+        if let Err(e) = work.after(ctx, &outcome).await {
+          outcome.status = Status::Failure(format!(
+            "After hook of Job {} failed: {}",
+            &ctx.job, e
+          ));
+        }
+        outcome
+      }
+      Err(e) => Outcome::new(Status::Failure(format!(
+        "Before hook of Job {} failed: {}",
+        &ctx.job, e
+      ))),
+    }
+  }
+}
+
+#[async_trait]
+#[typetag::serde(name = "Workflow")]
+impl Work for Workflow {
+  // Nodes already started are torn down when one fails to start, so none of
+  // them is left running.
+  async fn startup(&self) -> Result<(), String> {
+    let mut started: Vec<&dyn Work> = vec![];
+    for work in self.nodes.values() {
+      if let Err(e) = work.startup().await {
+        for work in started {
+          let _ = work.teardown().await;
+        }
+        return Err(e);
+      }
+      started.push(work.as_ref());
+    }
+    Ok(())
+  }
+
+  async fn func(&self, ctx: &JobContext<'_>) -> Status {
+    self.run(ctx).await.status
+  }
+
+  // Runs every node once all of its parents succeeded, nodes that become
+  // ready together run concurrently. When resuming, the nodes that already
+  // succeeded in that run are kept and only the rest run again.
+  async fn run(&self, ctx: &JobContext<'_>) -> Outcome {
+    if let Err(e) = self.validate() {
+      return Outcome::new(Status::Failure(e));
+    }
+
+    let (run_id, mut done) = match &ctx.resume {
+      Some(run_id) => {
+        let succeeded = self
+          .run_outcomes(ctx.ledger, &ctx.store, &ctx.job, run_id)
+          .into_iter()
+          .filter(|(_, outcome)| outcome.status == Status::Success)
+          .collect();
+        (run_id.clone(), succeeded)
+      }
+      None => (format!("{}-{}", &ctx.job, ctx.start), HashMap::new()),
+    };
+    ctx.progress.capture(String::from(RUN_ID), run_id.clone());
+    let mut steps = vec![];
+
+    loop {
+      let mut ready: Vec<&String> = self
+        .nodes
+        .keys()
+        .filter(|node| !done.contains_key(*node))
+        .filter(|node| {
+          self.parents(node).iter().all(|parent| {
+            done.get(*parent).map(|outcome: &Outcome| &outcome.status)
+              == Some(&Status::Success)
+          })
+        })
+        .collect();
+      if ready.is_empty() {
+        break;
+      }
+      ready.sort();
+
+      let contexts: Vec<JobContext> = ready
+        .iter()
+        .map(|node| {
+          let mut node_ctx = JobContext::new(
+            ctx.ledger,
+            ctx.store.clone(),
+            step_alias(&ctx.job, node),
+            ctx.scheduled_time,
+            ctx.start,
+            ctx.attempt,
+          );
+          node_ctx.cancel = ctx.cancel.clone();
+          node_ctx.logger = ctx.logger.clone();
+          for parent in self.parents(node) {
+            if let Some(output) = &done[parent].output {
+              node_ctx.inputs.insert(parent.clone(), output.clone());
+            }
+          }
+          node_ctx
+        })
+        .collect();
+      let outcomes = join_all(
+        ready
+          .iter()
+          .zip(contexts.iter())
+          .map(|(node, node_ctx)| self.run_node(node, node_ctx)),
+      )
+      .await;
+
+      for (node, mut outcome) in ready.into_iter().zip(outcomes) {
+        outcome
+          .captures
+          .insert(String::from(RUN_ID), run_id.clone());
+        ctx.progress.step(node.clone(), outcome.clone());
+        steps.push((node.clone(), outcome.clone()));
+        done.insert(node.clone(), outcome);
+      }
+    }
+
+    let mut failed: Vec<&String> = self
+      .nodes
+      .keys()
+      .filter(|node| match done.get(*node) {
+        Some(outcome) => outcome.status != Status::Success,
+        None => false,
+      })
+      .collect();
+    failed.sort();
+
+    let status = if failed.is_empty() {
+      Status::Success
+    } else {
+      Status::Failure(format!(
+        "Workflow {} failed at {:?}",
+        &self.alias, failed
+      ))
+    };
+    let mut outcome = Outcome::new(status);
+    outcome.captures.insert(String::from(RUN_ID), run_id);
+    outcome.steps = steps;
+    outcome
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    for work in self.nodes.values() {
+      work.teardown().await?;
+    }
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+impl Clone for Workflow {
+  fn clone(&self) -> Self {
+    Workflow {
+      alias: self.alias.clone(),
+      nodes: self
+        .nodes
+        .iter()
+        .map(|(alias, work)| (alias.clone(), work.vclone()))
+        .collect(),
+      edges: self.edges.clone(),
+    }
+  }
+}
+
+impl Debug for Workflow {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Workflow")
+      .field("alias", &self.alias)
+      .field("nodes", &self.nodes.keys())
+      .field("edges", &self.edges)
+      .finish()
+  }
+}
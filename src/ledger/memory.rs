@@ -1,19 +1,20 @@
 use crate::job::Status;
-use crate::ledger::History;
+use crate::ledger::{ExecutionRecord, History, Query, Retention, Summary};
 
-use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Debug;
 
+// Positions in `ts` of the records of a job by status, by job, by store.
+type Index = HashMap<String, HashMap<String, HashMap<String, Vec<usize>>>>;
+
+/// Keeps every record once in `ts`, in the order they were recorded, with
+/// `data` pointing into it.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Ledger {
-  pub data: HashMap<
-    String,
-    HashMap<String, HashMap<String, Vec<(String, String, Status, i64)>>>,
-  >,
-  pub ts: Vec<(String, String, Status, i64)>,
+  pub data: Index,
+  pub ts: Vec<ExecutionRecord>,
+  pub summaries: HashMap<String, HashMap<String, Summary>>,
 }
 
 impl Ledger {
@@ -21,107 +22,110 @@ impl Ledger {
     Ledger {
       data: HashMap::new(),
       ts: vec![],
+      summaries: HashMap::new(),
     }
   }
-}
 
-#[typetag::serde]
-impl History for Ledger {
-  fn insert(
-    &mut self,
+  fn index(&mut self, position: usize) {
+    let record = &self.ts[position];
+    self
+      .data
+      .entry(record.store.clone())
+      .or_insert_with(HashMap::new)
+      .entry(record.job.clone())
+      .or_insert_with(HashMap::new)
+      .entry(record.status.to_string())
+      .or_insert_with(Vec::new)
+      .push(position);
+  }
+
+  // Records of a job with the given status, in the order they were recorded.
+  fn indexed<'a>(
+    &'a self,
     store: &String,
     job: &String,
     status: &Status,
-    time: &i64,
-  ) {
+  ) -> impl Iterator<Item = &'a ExecutionRecord> {
     self
-      .ts
-      .push((store.clone(), job.clone(), status.clone(), time.clone()));
-    match self.data.entry(store.clone()) {
-      Entry::Occupied(mut sentry) => {
-        let store_map = sentry.get_mut();
-        match store_map.entry(job.clone()) {
-          Entry::Occupied(mut jentry) => {
-            let job_map = jentry.get_mut();
-            match job_map.entry(status.to_string()) {
-              Entry::Occupied(mut entry) => {
-                let entries = entry.get_mut();
-                entries.push((
-                  store.clone(),
-                  job.clone(),
-                  status.clone(),
-                  time.clone(),
-                ));
-              }
-              Entry::Vacant(entry) => {
-                entry.insert(vec![(
-                  store.clone(),
-                  job.clone(),
-                  status.clone(),
-                  time.clone(),
-                )]);
-              }
-            }
-          }
-          Entry::Vacant(entry) => {
-            let mut status_map = HashMap::new();
-            status_map.insert(
-              status.to_string(),
-              vec![(store.clone(), job.clone(), status.clone(), time.clone())],
-            );
-            entry.insert(status_map);
-          }
-        }
-      }
-      Entry::Vacant(entry) => {
-        let mut job_map = HashMap::new();
-        let mut status_map = HashMap::new();
-        status_map.insert(
-          status.to_string(),
-          vec![(store.clone(), job.clone(), status.clone(), time.clone())],
-        );
-        job_map.insert(job.clone(), status_map);
-        entry.insert(job_map);
-      }
-    }
+      .data
+      .get(store)
+      .and_then(|store_map| store_map.get(job))
+      .and_then(|job_map| job_map.get(&status.to_string()))
+      .into_iter()
+      .flatten()
+      .map(move |position| &self.ts[*position])
+  }
+}
+
+#[typetag::serde]
+impl History for Ledger {
+  fn record(&mut self, record: &ExecutionRecord) {
+    self
+      .summaries
+      .entry(record.store.clone())
+      .or_insert_with(HashMap::new)
+      .entry(record.job.clone())
+      .or_insert_with(Summary::default)
+      .record(record);
+    self.ts.push(record.clone());
+    self.index(self.ts.len() - 1);
   }
 
   fn entry(
-    &mut self,
+    &self,
     store: &String,
     job: &String,
     status: &Status,
     time: &i64,
+    now: &i64,
   ) -> bool {
-    let now = Utc::now().timestamp_nanos();
-    match self.data.entry(store.clone()) {
-      Entry::Occupied(mut sentry) => {
-        let store_map = sentry.get_mut();
-        match store_map.entry(job.clone()) {
-          Entry::Occupied(mut jentry) => {
-            let job_map = jentry.get_mut();
-            match job_map.entry(status.to_string()) {
-              Entry::Occupied(entry) => {
-                let entries = entry.get();
-                let mut contains = false;
-                for (_s, _j, _sus, t) in entries {
-                  if (now - t) <= time.clone() {
-                    contains = true;
-                    break;
-                  }
-                }
-                contains
-              }
-              Entry::Vacant(_entry) => false,
-            }
-          }
-          Entry::Vacant(_entry) => false,
-        }
-      }
-      Entry::Vacant(_entry) => false,
+    self
+      .indexed(store, job, status)
+      .any(|record| (now - record.start) <= *time)
+  }
+
+  // Lookups for a job and status go through the `data` index, anything
+  // broader scans `ts`.
+  fn query(&self, query: &Query) -> Vec<ExecutionRecord> {
+    let entries = match (&query.store, &query.job, &query.status) {
+      (Some(store), Some(job), Some(status)) => self
+        .indexed(store, job, status)
+        .filter(|record| query.matches(record))
+        .cloned()
+        .collect(),
+      _ => self
+        .ts
+        .iter()
+        .filter(|record| query.matches(record))
+        .cloned()
+        .collect(),
+    };
+    query.paginate(entries)
+  }
+
+  // `ts` is pruned and `data` rebuilt from what's left.
+  fn compact(&mut self, retention: &Retention, now: i64) {
+    let ts = std::mem::take(&mut self.ts);
+    self.ts = retention.prune(
+      ts,
+      |r| (&r.store, &r.job, r.start),
+      |r| bincode::serialized_size(r).unwrap_or(0) as usize,
+      now,
+    );
+    self.data = HashMap::new();
+    for position in 0..self.ts.len() {
+      self.index(position);
     }
   }
 
+  fn summary(&self, store: &String, job: &String) -> Option<Summary> {
+    self
+      .summaries
+      .get(store)
+      .and_then(|store_map| store_map.get(job))
+      .cloned()
+  }
+
   fn vclone(&self) -> Box<dyn History> {
     Box::new(self.clone())
   }
@@ -1,9 +1,13 @@
 pub mod memory;
+pub mod sled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 
-use crate::job::Status;
+use crate::job::{Outcome, Status};
 
 #[derive(Serialize, Deserialize)]
 pub struct Ledger {
@@ -17,24 +21,398 @@ impl Ledger {
   }
 }
 
+/// One run of a job as kept in a History. `scheduled` is when the run was
+/// due and `start` when it actually began, `end` is unknown for runs recorded
+/// without executing, such as missed ones. Workflow steps share the run id of
+/// the workflow run they belong to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExecutionRecord {
+  pub run_id: String,
+  pub store: String,
+  pub job: String,
+  pub executor: Option<String>,
+  pub scheduled: i64,
+  pub start: i64,
+  pub end: Option<i64>,
+  pub attempt: u32,
+  pub status: Status,
+  pub outcome: Option<Outcome>,
+}
+
+impl ExecutionRecord {
+  pub fn new(store: &String, job: &String, status: &Status, time: i64) -> Self {
+    ExecutionRecord {
+      run_id: format!("{}-{}", job, time),
+      store: store.clone(),
+      job: job.clone(),
+      executor: None,
+      scheduled: time,
+      start: time,
+      end: None,
+      attempt: 1,
+      status: status.clone(),
+      outcome: None,
+    }
+  }
+
+  pub fn with_outcome(
+    store: &String,
+    job: &String,
+    outcome: &Outcome,
+    time: i64,
+  ) -> Self {
+    let mut record = ExecutionRecord::new(store, job, &outcome.status, time);
+    record.outcome = Some(outcome.clone());
+    record
+  }
+
+  pub fn duration(&self) -> Option<i64> {
+    self.end.map(|end| end - self.start)
+  }
+
+  // Why the run failed, if it did.
+  pub fn reason(&self) -> Option<&String> {
+    match &self.status {
+      Status::Failure(reason) => Some(reason),
+      _ => None,
+    }
+  }
+}
+
+/// Filters for the runs kept in a History. Unset fields match everything,
+/// statuses match on their variant, so any `Failure` matches any failure.
+/// Times are inclusive nanoseconds and match the start of a run, results are
+/// ordered by start time, oldest
+/// first unless `newest_first` is set, and paged with `offset` and `limit`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Query {
+  pub store: Option<String>,
+  pub job: Option<String>,
+  pub status: Option<Status>,
+  pub from: Option<i64>,
+  pub to: Option<i64>,
+  pub newest_first: bool,
+  pub offset: usize,
+  pub limit: Option<usize>,
+}
+
+impl Query {
+  pub fn new() -> Self {
+    Query::default()
+  }
+
+  pub fn store(mut self, store: &String) -> Self {
+    self.store = Some(store.clone());
+    self
+  }
+
+  pub fn job(mut self, job: &String) -> Self {
+    self.job = Some(job.clone());
+    self
+  }
+
+  pub fn status(mut self, status: &Status) -> Self {
+    self.status = Some(status.clone());
+    self
+  }
+
+  pub fn between(mut self, from: i64, to: i64) -> Self {
+    self.from = Some(from);
+    self.to = Some(to);
+    self
+  }
+
+  pub fn newest_first(mut self) -> Self {
+    self.newest_first = true;
+    self
+  }
+
+  pub fn page(mut self, offset: usize, limit: usize) -> Self {
+    self.offset = offset;
+    self.limit = Some(limit);
+    self
+  }
+
+  pub fn matches(&self, record: &ExecutionRecord) -> bool {
+    self.store.iter().all(|s| s == &record.store)
+      && self.job.iter().all(|j| j == &record.job)
+      && self
+        .status
+        .iter()
+        .all(|s| s.to_string() == record.status.to_string())
+      && self.from.iter().all(|from| record.start >= *from)
+      && self.to.iter().all(|to| record.start <= *to)
+  }
+
+  // Orders and pages records that already matched.
+  pub fn paginate(
+    &self,
+    mut entries: Vec<ExecutionRecord>,
+  ) -> Vec<ExecutionRecord> {
+    entries.sort_by_key(|record| record.start);
+    if self.newest_first {
+      entries.reverse();
+    }
+    let entries = entries.into_iter().skip(self.offset);
+    match self.limit {
+      Some(limit) => entries.take(limit).collect(),
+      None => entries.collect(),
+    }
+  }
+}
+
+/// How much of the raw history to keep. Every limit is optional, entries
+/// older than `max_age` nanoseconds are dropped first, then all but the
+/// newest `max_per_job` entries of each job, then all but the newest
+/// `max_entries` overall, then the oldest entries until the rest take at most
+/// `max_bytes` as the History stores them. The scheduler compacts its ledger
+/// every `interval` nanoseconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Retention {
+  pub max_per_job: Option<usize>,
+  pub max_age: Option<i64>,
+  pub max_entries: Option<usize>,
+  pub max_bytes: Option<usize>,
+  pub interval: i64,
+}
+
+impl Retention {
+  pub fn new(interval: i64) -> Self {
+    Retention {
+      interval,
+      ..Retention::default()
+    }
+  }
+
+  // Keeps the entries the policy retains, ordered by time. `key` gives an
+  // entry's (store, job, time) and `size` how many bytes it takes.
+  pub fn prune<T, F, S>(
+    &self,
+    entries: Vec<T>,
+    key: F,
+    size: S,
+    now: i64,
+  ) -> Vec<T>
+  where
+    F: Fn(&T) -> (&String, &String, i64),
+    S: Fn(&T) -> usize,
+  {
+    let mut entries: Vec<T> = entries
+      .into_iter()
+      .filter(|entry| match self.max_age {
+        Some(age) => now - key(entry).2 <= age,
+        None => true,
+      })
+      .collect();
+    entries.sort_by_key(|entry| key(entry).2);
+
+    if let Some(max) = self.max_per_job {
+      let mut seen: HashMap<(String, String), usize> = HashMap::new();
+      let mut kept = vec![];
+      for entry in entries.into_iter().rev() {
+        let (store, job, _) = key(&entry);
+        let count = seen.entry((store.clone(), job.clone())).or_insert(0);
+        *count += 1;
+        if *count <= max {
+          kept.push(entry);
+        }
+      }
+      kept.reverse();
+      entries = kept;
+    }
+
+    if let Some(max) = self.max_entries {
+      let excess = entries.len().saturating_sub(max);
+      entries.drain(..excess);
+    }
+
+    if let Some(max) = self.max_bytes {
+      let mut total = 0;
+      let kept = entries
+        .iter()
+        .rev()
+        .take_while(|entry| {
+          total += size(entry);
+          total <= max
+        })
+        .count();
+      let excess = entries.len() - kept;
+      entries.drain(..excess);
+    }
+    entries
+  }
+}
+
+/// What a History remembers about a job regardless of pruning: how many runs
+/// ended with each status, keyed like Status::to_string, and the latest one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Summary {
+  pub counts: BTreeMap<String, usize>,
+  pub last_run: Option<i64>,
+  pub last_status: Option<Status>,
+  pub last_outcome: Option<Outcome>,
+}
+
+impl Summary {
+  pub fn record(&mut self, record: &ExecutionRecord) {
+    *self.counts.entry(record.status.to_string()).or_insert(0) += 1;
+    if self.last_run.iter().all(|last| record.start >= *last) {
+      self.last_run = Some(record.start);
+      self.last_status = Some(record.status.clone());
+      if record.outcome.is_some() {
+        self.last_outcome = record.outcome.clone();
+      }
+    }
+  }
+}
+
 #[typetag::serde(tag = "type")]
 pub trait History: Send + Sync {
+  fn record(&mut self, record: &ExecutionRecord);
+
+  // Records a run known only by its status, such as a missed one.
   fn insert(
     &mut self,
     store: &String,
     job: &String,
     status: &Status,
     time: &i64,
-  );
+  ) {
+    self.record(&ExecutionRecord::new(store, job, status, *time))
+  }
 
   fn entry(
-    &mut self,
+    &self,
     store: &String,
     job: &String,
     status: &Status,
     time: &i64,
+    now: &i64,
   ) -> bool;
 
+  // Records a run along with what the job captured.
+  fn insert_outcome(
+    &mut self,
+    store: &String,
+    job: &String,
+    outcome: &Outcome,
+    time: &i64,
+  ) {
+    self.record(&ExecutionRecord::with_outcome(store, job, outcome, *time))
+  }
+
+  // Outcomes of a job's runs by start time, oldest first.
+  fn outcomes(&self, store: &String, job: &String) -> Vec<(i64, Outcome)> {
+    self
+      .query(&Query::new().store(store).job(job))
+      .into_iter()
+      .filter_map(|record| Some((record.start, record.outcome?)))
+      .collect()
+  }
+
+  // Runs matching the query. Histories that can't be queried return nothing,
+  // so the helpers below find nothing.
+  fn query(&self, _query: &Query) -> Vec<ExecutionRecord> {
+    vec![]
+  }
+
+  // Runs of a job started between two times, inclusive.
+  fn runs(
+    &self,
+    store: &String,
+    job: &String,
+    from: i64,
+    to: i64,
+  ) -> Vec<ExecutionRecord> {
+    self.query(&Query::new().store(store).job(job).between(from, to))
+  }
+
+  // Latest `n` runs of a job, newest first.
+  fn last_runs(
+    &self,
+    store: &String,
+    job: &String,
+    n: usize,
+  ) -> Vec<ExecutionRecord> {
+    self.query(&Query::new().store(store).job(job).newest_first().page(0, n))
+  }
+
+  // Latest run of a job that ended with the status's variant.
+  fn last_with_status(
+    &self,
+    store: &String,
+    job: &String,
+    status: &Status,
+  ) -> Option<ExecutionRecord> {
+    let query = Query::new()
+      .store(store)
+      .job(job)
+      .status(status)
+      .newest_first()
+      .page(0, 1);
+    self.query(&query).into_iter().next()
+  }
+
+  fn last_success(
+    &self,
+    store: &String,
+    job: &String,
+  ) -> Option<ExecutionRecord> {
+    self.last_with_status(store, job, &Status::Success)
+  }
+
+  fn last_failure(
+    &self,
+    store: &String,
+    job: &String,
+  ) -> Option<ExecutionRecord> {
+    self.last_with_status(store, job, &Status::Failure(String::new()))
+  }
+
+  // Number of matching runs per status, keyed like Status::to_string. Paging
+  // is ignored.
+  fn counts(&self, query: &Query) -> BTreeMap<String, usize> {
+    let mut query = query.clone();
+    query.offset = 0;
+    query.limit = None;
+    let mut counts = BTreeMap::new();
+    for record in self.query(&query) {
+      *counts.entry(record.status.to_string()).or_insert(0) += 1;
+    }
+    counts
+  }
+
+  // Latest `n` outcomes of a job, newest first.
+  fn last_outcomes(
+    &self,
+    store: &String,
+    job: &String,
+    n: usize,
+  ) -> Vec<(i64, Outcome)> {
+    self
+      .outcomes(store, job)
+      .into_iter()
+      .rev()
+      .take(n)
+      .collect()
+  }
+
+  // Prunes raw entries the policy doesn't retain, summaries are kept.
+  fn compact(&mut self, _retention: &Retention, _now: i64) {}
+
+  fn summary(&self, _store: &String, _job: &String) -> Option<Summary> {
+    None
+  }
+
+  // Latest output a job handed downstream.
+  fn output(&self, store: &String, job: &String) -> Option<String> {
+    self
+      .outcomes(store, job)
+      .into_iter()
+      .rev()
+      .find_map(|(_time, outcome)| outcome.output)
+  }
+
   fn vclone(&self) -> Box<dyn History>;
 }
 
@@ -55,24 +433,3 @@ impl Debug for Ledger {
       .finish()
   }
 }
-
-// Bincode/Sled scraps
-// use serde::{Deserialize, Serialize};
-// use sled::Db;
-
-// let db = sled::open("/quick_scope");
-// pub fn serialize<T: Serialize>(value: &T, db = &mut sled::Db) -> Result<Vec<u8>> {
-//    let val = bincode::options()
-//         .big_endian()
-//         .serialize(&value)
-//         .map_err(|e| e.into());
-
-//   db.insert(key, value);
-// }
-
-// pub fn deserialize<'a, T: Deserialize<'a>>(value: &'a [u8]) -> Result<T> {
-//     bincode::options()
-//         .big_endian()
-//         .deserialize(&value)
-//         .map_err(|e| e.into())
-// }
@@ -0,0 +1,243 @@
+use crate::job::Status;
+use crate::ledger::{ExecutionRecord, History, Query, Retention, Summary};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+// Runs are keyed by store, job and start time, followed by a sequence number
+// so runs at the same time don't overwrite each other. Aliases are
+// terminated by a zero byte and times are stored big endian with the sign bit
+// flipped, so keys sort by (store, job, time) and a job's runs in a time range
+// are a single range scan.
+const RUNS: &str = "runs";
+const SUMMARIES: &str = "summaries";
+
+// Raw key, parsed (store, job, time) and what was read from the value of
+// tree entries.
+type Scanned<T> = Vec<(Vec<u8>, (String, String, i64), T)>;
+
+fn prefix(store: &String, job: Option<&String>) -> Vec<u8> {
+  let mut key = store.as_bytes().to_vec();
+  key.push(0);
+  if let Some(job) = job {
+    key.extend_from_slice(job.as_bytes());
+    key.push(0);
+  }
+  key
+}
+
+fn time_bytes(time: i64) -> [u8; 8] {
+  ((time as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+fn key(store: &String, job: &String, time: i64, seq: u64) -> Vec<u8> {
+  let mut key = prefix(store, Some(job));
+  key.extend_from_slice(&time_bytes(time));
+  key.extend_from_slice(&seq.to_be_bytes());
+  key
+}
+
+fn parse_key(key: &[u8]) -> Option<(String, String, i64)> {
+  let mut parts = key.splitn(3, |byte| *byte == 0);
+  let store = String::from_utf8(parts.next()?.to_vec()).ok()?;
+  let job = String::from_utf8(parts.next()?.to_vec()).ok()?;
+  let rest = parts.next()?;
+  if rest.len() < 8 {
+    return None;
+  }
+  let mut time = [0; 8];
+  time.copy_from_slice(&rest[..8]);
+  Some((store, job, (u64::from_be_bytes(time) ^ (1 << 63)) as i64))
+}
+
+/// A History kept in a sled database at `path`, so it survives restarts
+/// independently of the scheduler snapshot. Only the path is serialized, the
+/// database is opened on first use.
+#[derive(Serialize, Deserialize)]
+pub struct Ledger {
+  pub path: String,
+  #[serde(skip)]
+  db: Arc<Mutex<Option<::sled::Db>>>,
+}
+
+impl Ledger {
+  pub fn new(path: String) -> Result<Self, String> {
+    let ledger = Ledger {
+      path,
+      db: Arc::new(Mutex::new(None)),
+    };
+    ledger.db()?;
+    Ok(ledger)
+  }
+
+  fn db(&self) -> Result<::sled::Db, String> {
+    let mut db = self.db.lock().unwrap();
+    if db.is_none() {
+      *db = Some(::sled::open(&self.path).map_err(|e| {
+        format!("Unable to open ledger at {}: {}", &self.path, e)
+      })?);
+    }
+    Ok(db.clone().unwrap())
+  }
+
+  fn tree(&self, name: &str) -> Option<::sled::Tree> {
+    self.db().ok()?.open_tree(name).ok()
+  }
+
+  fn next_id(&self) -> u64 {
+    self
+      .db()
+      .ok()
+      .and_then(|db| db.generate_id().ok())
+      .unwrap_or(0)
+  }
+
+  // Matching entries of a tree, decoded, ordered by key.
+  fn scan<T>(&self, name: &str, query: &Query) -> Scanned<T>
+  where
+    T: for<'de> Deserialize<'de>,
+  {
+    let tree = match self.tree(name) {
+      Some(tree) => tree,
+      None => return vec![],
+    };
+    let entries = match (&query.store, &query.job) {
+      (Some(store), Some(job)) => {
+        let start = key(store, job, query.from.unwrap_or(i64::MIN), 0);
+        let end = key(store, job, query.to.unwrap_or(i64::MAX), u64::MAX);
+        tree.range(start..=end)
+      }
+      (Some(store), None) => tree.scan_prefix(prefix(store, None)),
+      _ => tree.iter(),
+    };
+    entries
+      .filter_map(Result::ok)
+      .filter_map(|(k, v)| {
+        let parsed = parse_key(&k)?;
+        let value = bincode::deserialize(&v).ok()?;
+        Some((k.to_vec(), parsed, value))
+      })
+      .collect()
+  }
+
+  fn update_summary<F>(&self, store: &String, job: &String, update: F)
+  where
+    F: Fn(&mut Summary),
+  {
+    if let Some(tree) = self.tree(SUMMARIES) {
+      let key = prefix(store, Some(job));
+      let _ = tree.update_and_fetch(key, |old| {
+        let mut summary: Summary = old
+          .and_then(|bytes| bincode::deserialize(bytes).ok())
+          .unwrap_or_default();
+        update(&mut summary);
+        bincode::serialize(&summary).ok()
+      });
+    }
+  }
+
+  // Removes the entries of a tree the policy doesn't retain.
+  fn prune(&self, name: &str, retention: &Retention, now: i64) {
+    let tree = match self.tree(name) {
+      Some(tree) => tree,
+      None => return,
+    };
+    let entries: Scanned<usize> = tree
+      .iter()
+      .filter_map(Result::ok)
+      .filter_map(|(k, v)| {
+        Some((k.to_vec(), parse_key(&k)?, k.len() + v.len()))
+      })
+      .collect();
+    let kept: HashSet<Vec<u8>> = retention
+      .prune(
+        entries.clone(),
+        |(_, (s, j, t), _)| (s, j, *t),
+        |(_, _, size)| *size,
+        now,
+      )
+      .into_iter()
+      .map(|(k, _, _)| k)
+      .collect();
+    for (k, _, _) in entries {
+      if !kept.contains(&k) {
+        let _ = tree.remove(k);
+      }
+    }
+  }
+}
+
+#[typetag::serde(name = "SledLedger")]
+impl History for Ledger {
+  fn record(&mut self, record: &ExecutionRecord) {
+    let (store, job) = (&record.store, &record.job);
+    if let (Some(tree), Ok(value)) =
+      (self.tree(RUNS), bincode::serialize(record))
+    {
+      let _ = tree.insert(key(store, job, record.start, self.next_id()), value);
+    }
+    self.update_summary(store, job, |summary| summary.record(record));
+    if let Ok(db) = self.db() {
+      let _ = db.flush();
+    }
+  }
+
+  fn entry(
+    &self,
+    store: &String,
+    job: &String,
+    status: &Status,
+    time: &i64,
+    now: &i64,
+  ) -> bool {
+    let query = Query::new()
+      .store(store)
+      .job(job)
+      .status(status)
+      .between(now - time, i64::MAX);
+    !self.query(&query).is_empty()
+  }
+
+  fn query(&self, query: &Query) -> Vec<ExecutionRecord> {
+    let entries = self
+      .scan(RUNS, query)
+      .into_iter()
+      .map(|(_, _, record)| record)
+      .filter(|record| query.matches(record))
+      .collect();
+    query.paginate(entries)
+  }
+
+  fn compact(&mut self, retention: &Retention, now: i64) {
+    self.prune(RUNS, retention, now);
+    if let Ok(db) = self.db() {
+      let _ = db.flush();
+    }
+  }
+
+  fn summary(&self, store: &String, job: &String) -> Option<Summary> {
+    let bytes = self.tree(SUMMARIES)?.get(prefix(store, Some(job))).ok()??;
+    bincode::deserialize(&bytes).ok()
+  }
+
+  fn vclone(&self) -> Box<dyn History> {
+    Box::new(self.clone())
+  }
+}
+
+impl Clone for Ledger {
+  fn clone(&self) -> Self {
+    Ledger {
+      path: self.path.clone(),
+      db: self.db.clone(),
+    }
+  }
+}
+
+impl Debug for Ledger {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Ledger").field("path", &self.path).finish()
+  }
+}
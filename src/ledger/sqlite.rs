@@ -0,0 +1,341 @@
+use crate::job::{Outcome, Status};
+use crate::ledger::{ExecutionRecord, History, Query, Retention, Summary};
+
+use rusqlite::types::{ToSql, Value as SqlValue};
+use rusqlite::{params, Connection, OptionalExtension, NO_PARAMS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+/// Schema of the database, every run is a row in `executions`:
+///
+/// - `run_id` the run the row belongs to, shared by a workflow's steps
+/// - `store`, `job` aliases of the store and job that ran
+/// - `executor` alias of the executor that ran it, NULL when it didn't run
+/// - `attempt` how many times in a row the job has been tried, from 1
+/// - `status` the status's variant, as Status::to_string
+/// - `reason` why the run failed, NULL unless `status` is Failure
+/// - `scheduled`, `start`, `end` nanoseconds since the epoch, when the run was
+///   due, began and ended, `end` is NULL when unknown
+/// - `duration` `end - start` in nanoseconds, NULL when unknown
+/// - `output` the payload handed to downstream jobs, if any
+/// - `outcome` the whole outcome as JSON text, NULL for runs recorded without
+///   one. Its `captures` object holds what the job reported, such as
+///   `exit_code`, `stdout` and `stderr` for system jobs or `status`,
+///   `latency` and `body` for network jobs, and `steps` the outcome of every
+///   workflow node. Read them with
+///   `json_extract(outcome, '$.captures.exit_code')` and the like.
+///
+/// `summaries` holds one JSON encoded Summary per job, kept when executions
+/// are pruned.
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS executions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  run_id TEXT NOT NULL,
+  store TEXT NOT NULL,
+  job TEXT NOT NULL,
+  executor TEXT,
+  attempt INTEGER NOT NULL,
+  status TEXT NOT NULL,
+  reason TEXT,
+  scheduled INTEGER NOT NULL,
+  start INTEGER NOT NULL,
+  \"end\" INTEGER,
+  duration INTEGER,
+  output TEXT,
+  outcome TEXT
+);
+CREATE INDEX IF NOT EXISTS executions_by_job
+  ON executions (store, job, start);
+CREATE TABLE IF NOT EXISTS summaries (
+  store TEXT NOT NULL,
+  job TEXT NOT NULL,
+  summary TEXT NOT NULL,
+  PRIMARY KEY (store, job)
+);
+";
+
+fn status(name: String, reason: Option<String>) -> Status {
+  match name.as_str() {
+    "Waiting" => Status::Waiting,
+    "Running" => Status::Running,
+    "Paused" => Status::Paused,
+    "Success" => Status::Success,
+    "Missed" => Status::Missed,
+    _ => Status::Failure(reason.unwrap_or_default()),
+  }
+}
+
+// Every run with the bytes its text and blob columns take.
+const SIZES: &str = "
+SELECT id, store, job, start,
+  LENGTH(CAST(run_id AS BLOB)) + LENGTH(CAST(store AS BLOB))
+  + LENGTH(CAST(job AS BLOB)) + LENGTH(CAST(status AS BLOB))
+  + COALESCE(LENGTH(CAST(executor AS BLOB)), 0)
+  + COALESCE(LENGTH(CAST(reason AS BLOB)), 0)
+  + COALESCE(LENGTH(CAST(output AS BLOB)), 0)
+  + COALESCE(LENGTH(outcome), 0)
+FROM executions
+";
+
+// Outcomes are JSON text, rows written before that hold bincode blobs.
+fn outcome(value: SqlValue) -> Option<Outcome> {
+  match value {
+    SqlValue::Text(json) => serde_json::from_str(&json).ok(),
+    SqlValue::Blob(bytes) => bincode::deserialize(&bytes).ok(),
+    _ => None,
+  }
+}
+
+/// A History kept in an SQLite file at `path`, see SCHEMA for its layout.
+/// Only the path is serialized, the database is opened on first use.
+#[derive(Serialize, Deserialize)]
+pub struct Ledger {
+  pub path: String,
+  #[serde(skip)]
+  conn: Arc<Mutex<Option<Connection>>>,
+}
+
+impl Ledger {
+  pub fn new(path: String) -> Result<Self, String> {
+    let ledger = Ledger {
+      path,
+      conn: Arc::new(Mutex::new(None)),
+    };
+    ledger.with_conn(|_| Ok(()))?;
+    Ok(ledger)
+  }
+
+  // Runs `f` on the connection, opening the database and creating the schema
+  // first if needed.
+  fn with_conn<T, F>(&self, f: F) -> Result<T, String>
+  where
+    F: FnOnce(&mut Connection) -> rusqlite::Result<T>,
+  {
+    let mut conn = self.conn.lock().unwrap();
+    if conn.is_none() {
+      let opened = Connection::open(&self.path)
+        .and_then(|c| c.execute_batch(SCHEMA).map(|_| c))
+        .map_err(|e| {
+          format!("Unable to open ledger at {}: {}", &self.path, e)
+        })?;
+      *conn = Some(opened);
+    }
+    f(conn.as_mut().unwrap()).map_err(|e| format!("{}", e))
+  }
+
+  fn insert_record(&self, record: &ExecutionRecord) -> Result<(), String> {
+    let (store, job) = (&record.store, &record.job);
+    let output = record
+      .outcome
+      .as_ref()
+      .and_then(|outcome| outcome.output.clone());
+    let encoded = record
+      .outcome
+      .as_ref()
+      .and_then(|outcome| serde_json::to_string(outcome).ok());
+    self.with_conn(|conn| {
+      let tx = conn.transaction()?;
+      tx.execute(
+        "INSERT INTO executions (run_id, store, job, executor, attempt,
+           status, reason, scheduled, start, \"end\", duration, output,
+           outcome)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+          record.run_id,
+          store,
+          job,
+          record.executor,
+          record.attempt,
+          record.status.to_string(),
+          record.reason(),
+          record.scheduled,
+          record.start,
+          record.end,
+          record.duration(),
+          output,
+          encoded
+        ],
+      )?;
+
+      let mut summary: Summary = tx
+        .query_row(
+          "SELECT summary FROM summaries WHERE store = ?1 AND job = ?2",
+          params![store, job],
+          |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+      summary.record(record);
+      tx.execute(
+        "INSERT OR REPLACE INTO summaries (store, job, summary)
+         VALUES (?1, ?2, ?3)",
+        params![store, job, serde_json::to_string(&summary).unwrap()],
+      )?;
+      tx.commit()
+    })
+  }
+
+  // Rows matching the query, in the query's order and page.
+  fn select(&self, query: &Query) -> Vec<ExecutionRecord> {
+    let mut filters = vec![];
+    let mut values: Vec<Box<dyn ToSql>> = vec![];
+    if let Some(store) = &query.store {
+      values.push(Box::new(store.clone()));
+      filters.push(format!("store = ?{}", values.len()));
+    }
+    if let Some(job) = &query.job {
+      values.push(Box::new(job.clone()));
+      filters.push(format!("job = ?{}", values.len()));
+    }
+    if let Some(status) = &query.status {
+      values.push(Box::new(status.to_string()));
+      filters.push(format!("status = ?{}", values.len()));
+    }
+    if let Some(from) = query.from {
+      values.push(Box::new(from));
+      filters.push(format!("start >= ?{}", values.len()));
+    }
+    if let Some(to) = query.to {
+      values.push(Box::new(to));
+      filters.push(format!("start <= ?{}", values.len()));
+    }
+    let order = if query.newest_first { "DESC" } else { "ASC" };
+    let sql = format!(
+      "SELECT run_id, store, job, executor, attempt, status, reason,
+         scheduled, start, \"end\", outcome FROM executions
+       {} ORDER BY start {}, id {} LIMIT {} OFFSET {}",
+      if filters.is_empty() {
+        String::new()
+      } else {
+        format!("WHERE {}", filters.join(" AND "))
+      },
+      order,
+      order,
+      query.limit.map_or(-1, |limit| limit as i64),
+      query.offset
+    );
+
+    self
+      .with_conn(|conn| {
+        let mut stmt = conn.prepare(&sql)?;
+        let rows =
+          stmt.query_map(values.iter().map(|v| v.as_ref()), |row| {
+            Ok(ExecutionRecord {
+              run_id: row.get(0)?,
+              store: row.get(1)?,
+              job: row.get(2)?,
+              executor: row.get(3)?,
+              attempt: row.get(4)?,
+              status: status(row.get(5)?, row.get(6)?),
+              scheduled: row.get(7)?,
+              start: row.get(8)?,
+              end: row.get(9)?,
+              outcome: outcome(row.get(10)?),
+            })
+          })?;
+        rows.collect()
+      })
+      .unwrap_or_default()
+  }
+}
+
+#[typetag::serde(name = "SqliteLedger")]
+impl History for Ledger {
+  fn record(&mut self, record: &ExecutionRecord) {
+    let _ = self.insert_record(record);
+  }
+
+  fn entry(
+    &self,
+    store: &String,
+    job: &String,
+    status: &Status,
+    time: &i64,
+    now: &i64,
+  ) -> bool {
+    let query = Query::new()
+      .store(store)
+      .job(job)
+      .status(status)
+      .between(now - time, i64::MAX)
+      .page(0, 1);
+    !self.select(&query).is_empty()
+  }
+
+  fn query(&self, query: &Query) -> Vec<ExecutionRecord> {
+    self.select(query)
+  }
+
+  fn compact(&mut self, retention: &Retention, now: i64) {
+    let _ = self.with_conn(|conn| {
+      let rows: Vec<(i64, String, String, i64, i64)> = {
+        let mut stmt = conn.prepare(SIZES)?;
+        let rows = stmt.query_map(NO_PARAMS, |row| {
+          Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+          ))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()?
+      };
+      let kept: HashSet<i64> = retention
+        .prune(
+          rows.clone(),
+          |(_, s, j, t, _)| (s, j, *t),
+          |(_, _, _, _, size)| *size as usize,
+          now,
+        )
+        .into_iter()
+        .map(|(id, _, _, _, _)| id)
+        .collect();
+
+      let tx = conn.transaction()?;
+      for (id, _, _, _, _) in rows {
+        if !kept.contains(&id) {
+          tx.execute("DELETE FROM executions WHERE id = ?1", params![id])?;
+        }
+      }
+      tx.commit()
+    });
+  }
+
+  fn summary(&self, store: &String, job: &String) -> Option<Summary> {
+    let json: String = self
+      .with_conn(|conn| {
+        conn
+          .query_row(
+            "SELECT summary FROM summaries WHERE store = ?1 AND job = ?2",
+            params![store, job],
+            |row| row.get(0),
+          )
+          .optional()
+      })
+      .ok()??;
+    serde_json::from_str(&json).ok()
+  }
+
+  fn vclone(&self) -> Box<dyn History> {
+    Box::new(self.clone())
+  }
+}
+
+impl Clone for Ledger {
+  fn clone(&self) -> Self {
+    Ledger {
+      path: self.path.clone(),
+      conn: self.conn.clone(),
+    }
+  }
+}
+
+impl Debug for Ledger {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Ledger").field("path", &self.path).finish()
+  }
+}
@@ -1,8 +1,13 @@
+pub mod admin;
+pub mod clock;
 pub mod event;
 pub mod executor;
 pub mod job;
 pub mod ledger;
 pub mod logger;
+pub mod metrics;
 pub mod scheduler;
+pub mod secret;
 pub mod store;
+mod trace;
 pub mod trigger;
\ No newline at end of file
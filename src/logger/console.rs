@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logger::{Emit, Format, Record};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Stream {
+  Stdout,
+  Stderr,
+}
+
+// Prints records to stdout or stderr, colored when they're text.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sink {
+  pub stream: Stream,
+}
+
+impl Sink {
+  pub fn stdout() -> Self {
+    Sink {
+      stream: Stream::Stdout,
+    }
+  }
+
+  pub fn stderr() -> Self {
+    Sink {
+      stream: Stream::Stderr,
+    }
+  }
+}
+
+#[typetag::serde(name = "ConsoleSink")]
+impl Emit for Sink {
+  fn emit(&self, record: &Record, format: Format) -> Result<(), String> {
+    let line = record.render(format, true);
+    match self.stream {
+      Stream::Stdout => println!("{}", line),
+      Stream::Stderr => eprintln!("{}", line),
+    }
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Emit> {
+    Box::new(self.clone())
+  }
+}
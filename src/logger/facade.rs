@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logger::{Emit, Format, Level, Record};
+
+// Hands records to the `log` crate under `target`, so they end up wherever
+// the host application's logger sends them. The format is left to that
+// logger.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sink {
+  pub target: String,
+}
+
+impl Sink {
+  pub fn new() -> Self {
+    Sink {
+      target: String::from("horoscope"),
+    }
+  }
+}
+
+fn level(level: Level) -> log::Level {
+  match level {
+    Level::Trace => log::Level::Trace,
+    Level::Debug => log::Level::Debug,
+    Level::Info => log::Level::Info,
+    Level::Warn => log::Level::Warn,
+    Level::Error => log::Level::Error,
+  }
+}
+
+#[typetag::serde(name = "FacadeSink")]
+impl Emit for Sink {
+  fn emit(&self, record: &Record, _format: Format) -> Result<(), String> {
+    log::log!(
+      target: self.target.as_str(),
+      level(record.level),
+      "{}",
+      record.message
+    );
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Emit> {
+    Box::new(self.clone())
+  }
+}
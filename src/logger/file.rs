@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::logger::{Emit, Format, Record};
+
+// Appends records to the file at `path`. Once a record would grow the file
+// past `max_bytes` it's rotated: `path` moves to `path.1`, `path.1` to
+// `path.2` and so on, keeping at most `keep` rotated files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Sink {
+  pub path: String,
+  pub max_bytes: Option<u64>,
+  pub keep: usize,
+}
+
+impl Sink {
+  pub fn new(path: String) -> Self {
+    Sink {
+      path,
+      max_bytes: None,
+      keep: 0,
+    }
+  }
+
+  pub fn rotate(path: String, max_bytes: u64, keep: usize) -> Self {
+    Sink {
+      path,
+      max_bytes: Some(max_bytes),
+      keep,
+    }
+  }
+
+  fn rotated(&self, n: usize) -> String {
+    format!("{}.{}", &self.path, n)
+  }
+
+  fn roll(&self) -> Result<(), String> {
+    if self.keep == 0 {
+      return fs::remove_file(&self.path).map_err(|e| format!("{}", e));
+    }
+    let _ = fs::remove_file(self.rotated(self.keep));
+    for n in (1..self.keep).rev() {
+      let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+    }
+    fs::rename(&self.path, self.rotated(1)).map_err(|e| format!("{}", e))
+  }
+}
+
+#[typetag::serde(name = "FileSink")]
+impl Emit for Sink {
+  fn emit(&self, record: &Record, format: Format) -> Result<(), String> {
+    let line = format!("{}\n", record.render(format, false));
+    if let Some(max_bytes) = self.max_bytes {
+      let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+      if size > 0 && size + line.len() as u64 > max_bytes {
+        self.roll().map_err(|e| {
+          format!("Unable to rotate log file {}: {}", &self.path, e)
+        })?;
+      }
+    }
+    OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .and_then(|mut file| file.write_all(line.as_bytes()))
+      .map_err(|e| format!("Unable to write log file {}: {}", &self.path, e))
+  }
+
+  fn vclone(&self) -> Box<dyn Emit> {
+    Box::new(self.clone())
+  }
+}
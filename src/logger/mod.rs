@@ -1,44 +1,250 @@
+pub mod console;
+pub mod facade;
+pub mod file;
+
 use chrono::prelude::*;
 use colored::*;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display};
 static LOGO: &str = "\n.......................................................................................\n.......................................................................................\n...................................::....:***:....:::..................................\n............................***:::*FFF***FFFFF***FFF*:::***............................\n.....................:::::.:FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF*.:::::.....................\n.....................:FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF*.....................\n................:*****FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF******................\n................:FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:................\n...........:*****FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF*****:...........\n............:FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF*............\n........::****FFFFFFFFFFFFFFFFFFFF*****FFFFFFFFF*****FFFFFFFFFFFFFFFFFFFFF***::........\n.........*FFFFFFFFFFFFFFFFFFFFF*:......:*FFFFF*:......:*FFFFFFFFFFFFFFFFFFFFF*:........\n.......::**FFFFFFFFFFFFFFFFFFF*:..:::...:FFFFF:...:::..:*FFFFFFFFFFFFFFFFFFFF*::.......\n.....:*FFFFFFFFFFFFFFFFFFFFFFF*:.INNM*..*FFFFF*:.*MNNV.:*FFFFFFFFFFFFFFFFFFFFFFF*:.....\n.....:FFFFFF******FFFFFFFFFFFFFF*:**::****FFFFFF*::**:**FFFFFFFFFFFFFF*****FFFFFF:.....\n......*FFFFFF*******FFF*****FFFFFFFFFFF*FVVVIF**FFFFFFFFFFF*****FFF*******FFFFFF*......\n.......:*FFFF*::*****FF***::::***************************:::::**FF*****::*FFFF*:.......\n.........:*FFF*...:*::**FF****FFFFFFF**::......:**FFFFFFF****FF**:.*::..*FFF*:.........\n...........:**F*........::*FFFFFFFFFFFFF*:...:*FFFFFFFFFFFFF*::........*F**:...........\n..............:*:.........:*FFFFFF******F*:::*F***F***FFFFF*:.........:*:..............\n............................:*FFFF***F*FFFFIFIFF*F***FFFF*::...........................\n...............................:*F*****FI$$MMM$IF****F*:...............................\n...............................:***IFVIF$M$$$MMVIVVVF$F*...............................\n..............................*I$**$FIFF$I$M$IVIFFIVVF$V*..............................\n..............................**FFFMMIFVMVV$VV$VIFMM$FF**..............................\n.................................:****IV$$$$$$$VV***::.................................\n.......................................................................................";
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub enum Filter {
+// The declaration order isn't the severity order, use `severity` to compare
+// levels.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Level {
   Info,
   Warn,
   Error,
+  Debug,
+  Trace,
+}
+
+/// The levels a logger drops, under the name they had before `Level`.
+pub type Filter = Level;
+
+impl Level {
+  pub fn severity(&self) -> u8 {
+    match self {
+      Level::Trace => 0,
+      Level::Debug => 1,
+      Level::Info => 2,
+      Level::Warn => 3,
+      Level::Error => 4,
+    }
+  }
+}
+
+impl Display for Level {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Level::Trace => "TRACE",
+      Level::Debug => "DEBUG",
+      Level::Info => "INFO",
+      Level::Warn => "WARN",
+      Level::Error => "ERROR",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  Text,
+  // One JSON object per line with `time`, `level` and `message`.
+  Json,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+  pub level: Level,
+  pub time: DateTime<Utc>,
+  pub message: String,
+}
+
+impl Record {
+  pub fn new(level: Level, message: String) -> Self {
+    Record {
+      level,
+      time: Utc::now(),
+      message,
+    }
+  }
+
+  pub fn render(&self, format: Format, colored: bool) -> String {
+    match format {
+      Format::Json => serde_json::json!({
+        "time": self.time.to_rfc3339(),
+        "level": self.level.to_string(),
+        "message": self.message,
+      })
+      .to_string(),
+      Format::Text if colored => {
+        let level = self.level.to_string();
+        let level = match self.level {
+          Level::Trace => level.dimmed(),
+          Level::Debug => level.cyan(),
+          Level::Info => level.blue(),
+          Level::Warn => level.yellow(),
+          Level::Error => level.red(),
+        };
+        format!("{} [{}] {}", self.time, level.bold(), self.message)
+      }
+      Format::Text => {
+        format!("{} [{}] {}", self.time, self.level, self.message)
+      }
+    }
+  }
+}
+
+// Where log records end up. Sinks are handed every record that passed the
+// logger's filtering and render it as they see fit.
+#[typetag::serde(tag = "type")]
+pub trait Emit: Send + Sync {
+  fn emit(&self, record: &Record, format: Format) -> Result<(), String>;
+
+  fn vclone(&self) -> Box<dyn Emit>;
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// Records below `level` are dropped, as are records whose level is listed
+/// in `filter`, and nothing is logged unless `enabled` is set. Logs go to
+/// stdout unless other sinks are given. Lifecycle banners are debug records
+/// unless `banners` is set, then they and the logo are printed to stdout.
+#[derive(Serialize, Deserialize)]
 pub struct Logger {
   pub enabled: bool,
-  pub filter: Vec<Filter>,
+  pub filter: Vec<Level>,
+  pub level: Level,
+  pub format: Format,
+  pub sinks: Vec<Box<dyn Emit>>,
+  pub banners: bool,
 }
 
 impl Logger {
-  pub fn new(enabled: bool, filter: Vec<Filter>) -> Logger {
-    println!("{}", LOGO.blue());
-    Logger { enabled, filter }
+  pub fn new(enabled: bool, filter: Vec<Level>) -> Logger {
+    Logger {
+      enabled,
+      filter,
+      level: Level::Info,
+      format: Format::Text,
+      sinks: vec![Box::new(console::Sink::stdout())],
+      banners: false,
+    }
   }
-  pub fn info(&self, content: String) {
-    if !&self.enabled || self.filter.contains(&Filter::Info) {
-      ();
+
+  pub fn with_banners(mut self) -> Self {
+    self.banners = true;
+    self
+  }
+
+  pub fn with_level(mut self, level: Level) -> Self {
+    self.level = level;
+    self
+  }
+
+  pub fn with_format(mut self, format: Format) -> Self {
+    self.format = format;
+    self
+  }
+
+  pub fn with_sinks(mut self, sinks: Vec<Box<dyn Emit>>) -> Self {
+    self.sinks = sinks;
+    self
+  }
+
+  pub fn logs(&self, level: Level) -> bool {
+    self.enabled
+      && level.severity() >= self.level.severity()
+      && !self.filter.contains(&level)
+  }
+
+  pub fn log(&self, level: Level, content: String) {
+    if !self.logs(level) {
+      return;
+    }
+    let record = Record::new(level, content);
+    for sink in &self.sinks {
+      if let Err(e) = sink.emit(&record, self.format) {
+        eprintln!("Failed to emit log record: {}", e);
+      }
     }
-    println!("{} [{}] {}", "INFO".blue().bold(), Utc::now(), content);
+  }
+
+  pub fn trace(&self, content: String) {
+    self.log(Level::Trace, content)
+  }
+
+  pub fn debug(&self, content: String) {
+    self.log(Level::Debug, content)
+  }
+
+  pub fn info(&self, content: String) {
+    self.log(Level::Info, content)
   }
 
   pub fn warn(&self, content: String) {
-    if !&self.enabled || self.filter.contains(&Filter::Warn) {
-      ();
-    }
-    println!("{} [{}] {}", Utc::now(), "WARN".yellow().bold(), content);
+    self.log(Level::Warn, content)
   }
 
   pub fn err(&self, content: String) {
-    if !&self.enabled || self.filter.contains(&Filter::Error) {
-      ();
+    self.log(Level::Error, content)
+  }
+
+  pub fn logo(&self) {
+    if self.enabled && self.banners {
+      println!("{}", LOGO.blue());
+    }
+  }
+
+  pub fn banner(&self, content: String) {
+    if self.enabled && self.banners {
+      println!(
+        "{}",
+        format!("::::   {}   ::::", content)
+          .truecolor(0, 0, 0)
+          .bold()
+          .on_green()
+      );
+    } else {
+      self.debug(content)
     }
-    println!("{} [{}] {}", Utc::now(), "ERROR".red().bold(), content)
+  }
+}
+
+impl Clone for Logger {
+  fn clone(&self) -> Self {
+    Logger {
+      enabled: self.enabled,
+      filter: self.filter.clone(),
+      level: self.level,
+      format: self.format,
+      sinks: self.sinks.iter().map(|sink| sink.vclone()).collect(),
+      banners: self.banners,
+    }
+  }
+}
+
+// Sinks are compared by their serialized form.
+impl PartialEq for Logger {
+  fn eq(&self, other: &Self) -> bool {
+    self.enabled == other.enabled
+      && self.filter == other.filter
+      && self.level == other.level
+      && self.format == other.format
+      && self.banners == other.banners
+      && serde_json::to_value(&self.sinks).ok()
+        == serde_json::to_value(&other.sinks).ok()
+  }
+}
+
+impl Debug for Logger {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Logger")
+      .field("enabled", &self.enabled)
+      .field("filter", &self.filter)
+      .field("level", &self.level)
+      .field("format", &self.format)
+      .field("sinks", &self.sinks.len())
+      .field("banners", &self.banners)
+      .finish()
   }
 }
@@ -0,0 +1,317 @@
+use async_std::future;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::job::Status;
+use crate::ledger::ExecutionRecord;
+
+// Limits on requests for metrics, and how long to wait before accepting again
+// after failing to.
+const MAX_HEADER: usize = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+// Upper bounds, in seconds, of the buckets of every histogram.
+const BUCKETS: [f64; 14] = [
+  0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+  300.0,
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+  // Observations per bucket, not cumulative, the last one is +Inf.
+  pub counts: Vec<u64>,
+  pub sum: f64,
+  pub count: u64,
+}
+
+impl Default for Histogram {
+  fn default() -> Self {
+    Histogram {
+      counts: vec![0; BUCKETS.len() + 1],
+      sum: 0.0,
+      count: 0,
+    }
+  }
+}
+
+impl Histogram {
+  pub fn new() -> Self {
+    Histogram::default()
+  }
+
+  pub fn observe(&mut self, value: f64) {
+    let bucket = BUCKETS
+      .iter()
+      .position(|bound| value <= *bound)
+      .unwrap_or(BUCKETS.len());
+    self.counts[bucket] += 1;
+    self.sum += value;
+    self.count += 1;
+  }
+}
+
+// (store, executor)
+type Key = (String, String);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Registry {
+  pub executed: BTreeMap<Key, u64>,
+  pub succeeded: BTreeMap<Key, u64>,
+  pub failed: BTreeMap<Key, u64>,
+  pub durations: BTreeMap<Key, Histogram>,
+  pub lags: BTreeMap<Key, Histogram>,
+  pub jobs: BTreeMap<String, usize>,
+  pub in_flight: BTreeMap<String, i64>,
+}
+
+/// Counters, histograms and gauges about the runs of a scheduler, rendered in
+/// the Prometheus text format. Clones share the same registry, so a handle
+/// can be read while the scheduler keeps recording.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+  registry: Arc<Mutex<Registry>>,
+}
+
+fn seconds(nanos: i64) -> f64 {
+  nanos as f64 / 1_000_000_000.0
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+  let pairs: Vec<String> = pairs
+    .iter()
+    .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+    .collect();
+  format!("{{{}}}", pairs.join(","))
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {} {}", name, help);
+  let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counters(
+  out: &mut String,
+  name: &str,
+  help: &str,
+  map: &BTreeMap<Key, u64>,
+) {
+  header(out, name, "counter", help);
+  for ((store, executor), value) in map {
+    let labels = labels(&[("store", store), ("executor", executor)]);
+    let _ = writeln!(out, "{}{} {}", name, labels, value);
+  }
+}
+
+fn histograms(
+  out: &mut String,
+  name: &str,
+  help: &str,
+  map: &BTreeMap<Key, Histogram>,
+) {
+  header(out, name, "histogram", help);
+  for ((store, executor), histogram) in map {
+    let mut cumulative = 0;
+    for (i, count) in histogram.counts.iter().enumerate() {
+      cumulative += count;
+      let bound = match BUCKETS.get(i) {
+        Some(bound) => format!("{}", bound),
+        None => String::from("+Inf"),
+      };
+      let labels =
+        labels(&[("store", store), ("executor", executor), ("le", &bound)]);
+      let _ = writeln!(out, "{}_bucket{} {}", name, labels, cumulative);
+    }
+    let labels = labels(&[("store", store), ("executor", executor)]);
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels, histogram.count);
+  }
+}
+
+impl Metrics {
+  pub fn new() -> Self {
+    Metrics::default()
+  }
+
+  pub fn registry(&self) -> Registry {
+    self.registry.lock().unwrap().clone()
+  }
+
+  pub fn run_started(&self, executor: &String) {
+    let mut registry = self.registry.lock().unwrap();
+    *registry.in_flight.entry(executor.clone()).or_insert(0) += 1;
+  }
+
+  // Counts a finished run, timing it with the record's end and scheduled
+  // time when they're known.
+  pub fn run_finished(&self, record: &ExecutionRecord) {
+    let executor = record.executor.clone().unwrap_or_default();
+    let key = (record.store.clone(), executor.clone());
+    let mut registry = self.registry.lock().unwrap();
+    if let Some(in_flight) = registry.in_flight.get_mut(&executor) {
+      *in_flight -= 1;
+    }
+    *registry.executed.entry(key.clone()).or_insert(0) += 1;
+    match record.status {
+      Status::Success => {
+        *registry.succeeded.entry(key.clone()).or_insert(0) += 1
+      }
+      Status::Failure(_) => {
+        *registry.failed.entry(key.clone()).or_insert(0) += 1
+      }
+      _ => (),
+    }
+    if let Some(duration) = record.duration() {
+      registry
+        .durations
+        .entry(key.clone())
+        .or_insert_with(Histogram::new)
+        .observe(seconds(duration));
+    }
+    registry
+      .lags
+      .entry(key)
+      .or_insert_with(Histogram::new)
+      .observe(seconds(record.start - record.scheduled));
+  }
+
+  pub fn set_jobs(&self, store: &String, jobs: usize) {
+    self
+      .registry
+      .lock()
+      .unwrap()
+      .jobs
+      .insert(store.clone(), jobs);
+  }
+
+  pub fn remove_store(&self, store: &String) {
+    self.registry.lock().unwrap().jobs.remove(store);
+  }
+
+  pub fn render(&self) -> String {
+    let registry = self.registry();
+    let mut out = String::new();
+    counters(
+      &mut out,
+      "horoscope_jobs_executed_total",
+      "Runs executed.",
+      &registry.executed,
+    );
+    counters(
+      &mut out,
+      "horoscope_jobs_succeeded_total",
+      "Runs that succeeded.",
+      &registry.succeeded,
+    );
+    counters(
+      &mut out,
+      "horoscope_jobs_failed_total",
+      "Runs that failed.",
+      &registry.failed,
+    );
+    histograms(
+      &mut out,
+      "horoscope_job_duration_seconds",
+      "How long runs took.",
+      &registry.durations,
+    );
+    histograms(
+      &mut out,
+      "horoscope_scheduling_lag_seconds",
+      "How late runs started after they were due.",
+      &registry.lags,
+    );
+    header(&mut out, "horoscope_jobs", "gauge", "Jobs per store.");
+    for (store, jobs) in &registry.jobs {
+      let labels = labels(&[("store", store)]);
+      let _ = writeln!(out, "horoscope_jobs{} {}", labels, jobs);
+    }
+    header(
+      &mut out,
+      "horoscope_executions_in_flight",
+      "gauge",
+      "Runs currently executing per executor.",
+    );
+    for (executor, in_flight) in &registry.in_flight {
+      let labels = labels(&[("executor", executor)]);
+      let _ = writeln!(
+        out,
+        "horoscope_executions_in_flight{} {}",
+        labels, in_flight
+      );
+    }
+    out
+  }
+}
+
+// Reads the request up to the end of its headers, None when they're larger
+// than MAX_HEADER.
+async fn read_head(stream: &mut TcpStream) -> Option<Vec<u8>> {
+  let mut request = vec![];
+  let mut buf = [0; 1024];
+  while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+    if request.len() > MAX_HEADER {
+      return None;
+    }
+    match stream.read(&mut buf).await {
+      Ok(0) | Err(_) => break,
+      Ok(n) => request.extend_from_slice(&buf[..n]),
+    }
+  }
+  Some(request)
+}
+
+// Clients that don't send their headers within READ_TIMEOUT are dropped.
+async fn respond(mut stream: TcpStream, metrics: Metrics) {
+  let head = future::timeout(READ_TIMEOUT, read_head(&mut stream));
+  let response = match head.await {
+    Err(_) => return,
+    Ok(None) => String::from(
+      "HTTP/1.1 431 Request Header Fields Too Large\r\nContent-Length: 0\r\n\
+       Connection: close\r\n\r\n",
+    ),
+    Ok(Some(request)) if request.starts_with(b"GET /metrics ") => {
+      let body = metrics.render();
+      format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+      )
+    }
+    Ok(Some(_)) => String::from(
+      "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\
+       Connection: close\r\n\r\n",
+    ),
+  };
+  let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// Answers `GET /metrics` with the rendered metrics and anything else with a
+/// 404. Failing to accept a connection, such as when out of file descriptors,
+/// only drops that connection.
+pub async fn serve(listener: TcpListener, metrics: Metrics) {
+  let mut incoming = listener.incoming();
+  while let Some(stream) = incoming.next().await {
+    match stream {
+      Ok(stream) => {
+        task::spawn(respond(stream, metrics.clone()));
+      }
+      Err(_) => task::sleep(ACCEPT_BACKOFF).await,
+    }
+  }
+}
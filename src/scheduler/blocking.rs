@@ -1,226 +1,934 @@
 use async_channel::{Receiver, Sender};
 use async_trait::async_trait;
-use colored::*;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::fmt::Debug;
 
+use crate::clock::{Clock, SystemClock};
 // use crate::event::Event;
-use crate::executor::Executor;
-use crate::job::Work;
-use crate::ledger::{memory, Ledger};
+use crate::executor::{Cancellations, Executor};
+use crate::job::{
+  workflow, Job, JobContext, Misfire, MisfirePolicy, Outcome, Status, Work,
+};
+use crate::ledger::{memory, ExecutionRecord, Ledger, Retention};
 use crate::logger::Logger;
-use crate::scheduler::{Msg, Schedule, SchedulerState};
+use crate::metrics::Metrics;
+use crate::scheduler::{Msg, Read, Schedule, SchedulerState};
 use crate::store::Store;
+use crate::trace::{self, Instrument};
+use crate::trigger::{Trigger, TriggerContext, RECHECK_INTERVAL};
 // type Listener = Box<dyn Fn(Event) -> ()>;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+// Upper bound on missed fire times replayed for a job in one pass.
+const MAX_CATCH_UP: usize = 1000;
+
+// The parts of a scheduler every run reads or records into, borrowed apart
+// from its stores and executors so a job can run while it's borrowed from
+// them.
+struct Handles<'a> {
+  ledger: &'a mut Ledger,
+  cancellations: &'a Cancellations,
+  metrics: &'a Metrics,
+  logger: &'a Option<Logger>,
+  clock: &'a dyn Clock,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Scheduler {
   pub state: SchedulerState,
   pub ledger: Ledger,
   pub stores: HashMap<String, Store>,
   pub executors: HashMap<String, Executor>,
   pub logger: Option<Logger>,
+  pub clock: Box<dyn Clock>,
   pub dirty: bool,
+  // Min-heap of (wake time, store, job). Entries are never removed eagerly, a
+  // popped entry is ignored when its job is gone or no longer due. Jobs must
+  // go through add_store/add_job to be queued.
+  #[serde(skip)]
+  pub queue: BinaryHeap<Reverse<(i64, String, String)>>,
+  #[serde(skip)]
+  pub cancellations: Cancellations,
+  #[serde(skip)]
+  pub metrics: Metrics,
+  // Compaction policy for the ledger, checked after every pass over due
+  // jobs, and when it last ran.
+  pub retention: Option<Retention>,
+  pub last_compaction: i64,
 }
 
 impl Scheduler {
   pub fn new(id: String, logger: Option<Logger>) -> Self {
+    Scheduler::with_clock(id, logger, Box::new(SystemClock::new()))
+  }
+
+  pub fn with_clock(
+    id: String,
+    logger: Option<Logger>,
+    clock: Box<dyn Clock>,
+  ) -> Self {
     Scheduler {
       state: SchedulerState::Uninitialized,
       ledger: Ledger::new(id, Box::new(memory::Ledger::new())),
       stores: HashMap::new(),
       executors: HashMap::new(),
       logger,
+      clock,
       dirty: false,
+      queue: BinaryHeap::new(),
+      cancellations: Cancellations::new(),
+      metrics: Metrics::new(),
+      retention: None,
+      last_compaction: 0,
+    }
+  }
+
+  fn enqueue(&mut self, time: i64, store: &String, job: &String) {
+    self.queue.push(Reverse((time, store.clone(), job.clone())));
+  }
+
+  fn enqueue_store(&mut self, alias: &String) {
+    if let Some(store) = self.stores.get(alias) {
+      for (job_alias, job) in &store.jobs {
+        self.queue.push(Reverse((
+          job.start_time,
+          alias.clone(),
+          job_alias.clone(),
+        )));
+      }
+    }
+  }
+
+  fn rebuild_queue(&mut self) {
+    self.queue.clear();
+    let aliases: Vec<String> = self.stores.keys().cloned().collect();
+    for alias in aliases {
+      self.enqueue_store(&alias);
+    }
+  }
+
+  // Replays the triggers at every fire time the job missed, as if the
+  // scheduler had been running, and splits those fire times into runs to
+  // execute now and runs to drop according to the job's misfire policy. Runs
+  // are given by the time they were due.
+  async fn catch_up(
+    job: &mut Job,
+    misfire: &Misfire,
+    ledger: &Ledger,
+    store: &String,
+    now: i64,
+  ) -> (Vec<i64>, Vec<i64>, Option<i64>) {
+    let mut fired = vec![];
+    let mut time = job.start_time;
+    let next = loop {
+      let ctx = TriggerContext::new(
+        time,
+        ledger,
+        ledger.alias.clone(),
+        store.clone(),
+        job.alias.clone(),
+        job.last_run,
+        job.last_status.clone(),
+      );
+      let (should_run, next) = job.validate_triggers(&ctx).await;
+      if should_run {
+        fired.push(time);
+      }
+      match next {
+        Some(v) if v > time && now - v > misfire.grace => {
+          if fired.len() >= MAX_CATCH_UP {
+            break Some(v);
+          }
+          time = v;
+        }
+        _ => break next,
+      }
+    };
+
+    match misfire.policy {
+      MisfirePolicy::FireAll => (fired, vec![], next),
+      MisfirePolicy::FireOnce => match fired.pop() {
+        Some(time) => (vec![time], fired, next),
+        None => (vec![], fired, next),
+      },
+      MisfirePolicy::Skip => (vec![], fired, next),
+    }
+  }
+
+  // Starts a job up without recording a failure, for jobs that aren't
+  // scheduled yet.
+  async fn startup_job(
+    job: &mut Job,
+    logger: &Option<Logger>,
+  ) -> Result<(), String> {
+    let started = job.started;
+    match job.startup().await {
+      Ok(_) => {
+        if !started {
+          Scheduler::banner(logger, format!("Starting Job {}", &job.alias));
+        }
+        Ok(())
+      }
+      Err(e) => Err(format!("Failed to start up Job {}: {}", &job.alias, e)),
+    }
+  }
+
+  // Starts a job up, a failure is recorded in the ledger and the job stays
+  // stopped so it won't run.
+  async fn start_job(
+    job: &mut Job,
+    ledger: &mut Ledger,
+    logger: &Option<Logger>,
+    key: &String,
+    now: i64,
+  ) -> Result<(), String> {
+    match Scheduler::startup_job(job, logger).await {
+      Ok(_) => Ok(()),
+      Err(e) => {
+        Scheduler::record_failure(job, ledger, logger, key, now, &e);
+        Err(e)
+      }
+    }
+  }
+
+  // Tears a job down, a failure is recorded in the ledger.
+  async fn teardown_job(
+    job: &mut Job,
+    ledger: &mut Ledger,
+    logger: &Option<Logger>,
+    key: &String,
+    now: i64,
+  ) -> Result<(), String> {
+    let started = job.started;
+    match job.teardown().await {
+      Ok(_) => {
+        if started {
+          let content = format!("Tearing Down Job {}", &job.alias);
+          Scheduler::banner(logger, content);
+        }
+        Ok(())
+      }
+      Err(e) => {
+        let e = format!("Failed to tear down Job {}: {}", &job.alias, e);
+        Scheduler::record_failure(job, ledger, logger, key, now, &e);
+        Err(e)
+      }
+    }
+  }
+
+  // Takes the state of a loaded snapshot. The jobs of the stores being
+  // replaced are torn down first, a failure is only logged since their
+  // ledger is replaced too.
+  async fn restore(&mut self, snapshot: Scheduler) {
+    let now = self.clock.now();
+    for (key, store) in self.stores.iter_mut() {
+      for job in store.jobs.values_mut() {
+        let _ = Scheduler::teardown_job(
+          job,
+          &mut self.ledger,
+          &self.logger,
+          key,
+          now,
+        )
+        .await;
+      }
+    }
+    self.stores = snapshot.stores;
+    self.ledger = snapshot.ledger;
+    self.executors = snapshot.executors;
+    self.logger = snapshot.logger;
+    self.retention = snapshot.retention;
+    self.rebuild_queue();
+  }
+
+    fn banner(logger: &Option<Logger>, content: String) {
+    if let Some(logger) = logger {
+      logger.banner(content)
+    }
+  }
+
+  fn record_failure(
+    job: &Job,
+    ledger: &mut Ledger,
+    logger: &Option<Logger>,
+    key: &String,
+    now: i64,
+    e: &String,
+  ) {
+    ledger
+      .ledger
+      .insert(key, &job.alias, &Status::Failure(e.clone()), &now);
+    if let Some(logger) = logger {
+      logger.err(e.clone())
+    }
+  }
+
+  // Executes a job once and records its outcome, along with the outcome of
+  // every workflow step it ran, under the store's key in the ledger. Jobs
+  // that aren't started are started first, the run is skipped when that or
+  // the before hook fails.
+  async fn execute(
+    handles: Handles<'_>,
+    executor: &Executor,
+    job: &mut Job,
+    key: &String,
+    name: &String,
+    scheduled: i64,
+    resume: Option<String>,
+  ) {
+    let Handles {
+      ledger,
+      cancellations,
+      metrics,
+      logger,
+      clock,
+    } = handles;
+    let now = clock.now();
+    job.attempt = match &job.last_status {
+      Some(Status::Failure(_)) => job.attempt + 1,
+      _ => 1,
+    };
+    if let Err(e) =
+      Scheduler::start_job(job, ledger, logger, key, now).await
+    {
+      job.last_run = Some(now);
+      job.last_status = Some(Status::Failure(e));
+      return;
+    }
+    let mut ctx = JobContext::new(
+      ledger,
+      key.clone(),
+      job.alias.clone(),
+      scheduled,
+      now,
+      job.attempt,
+    );
+    ctx.cancel = cancellations.register(key, &job.alias);
+    ctx.logger = logger.clone();
+    ctx.resume = resume;
+    metrics.run_started(&job.executor);
+    let mut outcome = match job.job.before(&ctx).await {
+      Ok(_) => {
+        let mut outcome = executor.run(&job.job, &ctx, job.timeout).await;
+        if let Err(e) = job.job.after(&ctx, &outcome).await {
+          outcome.status = Status::Failure(format!(
+            "After hook of Job {} failed: {}",
+            &job.alias, e
+          ));
+        }
+        outcome
+      }
+      Err(e) => Outcome::new(Status::Failure(format!(
+        "Before hook of Job {} failed: {}",
+        &job.alias, e
+      ))),
+    };
+    cancellations.finish(key, &job.alias);
+    outcome.status = match outcome.status {
+      Status::Failure(e) => {
+        if let Some(logger) = logger {
+          logger.err(e.clone())
+        }
+        Status::Failure(e)
+      }
+      _ => {
+        if let Some(logger) = logger {
+          logger.info(format!(
+            "EXECUTING JOB {} FROM STORE {} SUCCEEDED",
+            &job.alias, name
+          ))
+        }
+        Status::Success
+      }
+    };
+
+    let mut record =
+      ExecutionRecord::with_outcome(key, &job.alias, &outcome, now);
+    if let Some(run_id) = outcome.captures.get(workflow::RUN_ID) {
+      record.run_id = run_id.clone();
+    }
+    record.executor = Some(job.executor.clone());
+    record.scheduled = scheduled;
+    record.end = Some(clock.now());
+    record.attempt = job.attempt;
+    for (node, step) in &outcome.steps {
+      ledger.ledger.record(&ExecutionRecord {
+        job: workflow::step_alias(&job.alias, node),
+        status: step.status.clone(),
+        outcome: Some(step.clone()),
+        ..record.clone()
+      });
+    }
+    ledger.ledger.record(&record);
+    metrics.run_finished(&record);
+    job.last_run = Some(now);
+    job.last_status = Some(outcome.status);
+  }
+
+  // Executes a job right away, outside of its schedule, resuming the given
+  // workflow run if any.
+  async fn run_now(
+    &mut self,
+    store_alias: String,
+    alias: String,
+    resume: Option<String>,
+  ) -> Result<(), String> {
+    let now = self.clock.now();
+    let store = match self.stores.get_mut(&store_alias) {
+      Some(store) => store,
+      None => {
+        return Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    };
+    let name = store.alias.clone();
+    let job = match store.jobs.get_mut(&alias) {
+      Some(job) => job,
+      None => {
+        return Err(format!(
+          "Failed to {} Job {}, it's not found in Store {}",
+          if resume.is_some() { "retry" } else { "run" },
+          &alias,
+          &name
+        ))
+      }
+    };
+    let executor = match self.executors.get(&job.executor) {
+      Some(executor) => executor,
+      None => {
+        return Err(format!(
+          "Executor {} was not found in the schedulers executors",
+          &job.executor
+        ))
+      }
+    };
+
+    let span = trace::job(&alias, &store_alias, &job.executor);
+    let run = trace::run(&span, &alias, now);
+    let handles = Handles {
+      ledger: &mut self.ledger,
+      cancellations: &self.cancellations,
+      metrics: &self.metrics,
+      logger: &self.logger,
+      clock: &*self.clock,
+    };
+    Scheduler::execute(
+      handles,
+      executor,
+      job,
+      &store_alias,
+      &name,
+      now,
+      resume,
+    )
+    .instrument(run)
+    .await;
+    self.dirty = true;
+    Ok(())
+  }
+
+  fn count_jobs(&self) {
+    for (alias, store) in &self.stores {
+      self.metrics.set_jobs(alias, store.jobs.len());
     }
   }
+
+  // Compacts the ledger once every retention interval.
+  fn compact_ledger(&mut self, now: i64) {
+    if let Some(retention) = &self.retention {
+      if now - self.last_compaction >= retention.interval {
+        self.ledger.ledger.compact(retention, now);
+        self.last_compaction = now;
+        self.dirty = true;
+      }
+    }
+  }
+
+  fn take_due(&mut self, now: i64) -> Vec<(String, String)> {
+    let mut due = vec![];
+    let mut seen = HashSet::new();
+    while let Some(Reverse((time, _, _))) = self.queue.peek() {
+      if *time > now {
+        break;
+      }
+      if let Some(Reverse((_, store, job))) = self.queue.pop() {
+        if seen.insert((store.clone(), job.clone())) {
+          due.push((store, job));
+        }
+      }
+    }
+    due
+  }
+}
+
+impl Clone for Scheduler {
+  fn clone(&self) -> Self {
+    Scheduler {
+      state: self.state.clone(),
+      ledger: self.ledger.clone(),
+      stores: self.stores.clone(),
+      executors: self.executors.clone(),
+      logger: self.logger.clone(),
+      clock: self.clock.vclone(),
+      dirty: self.dirty,
+      queue: self.queue.clone(),
+      cancellations: self.cancellations.clone(),
+      metrics: self.metrics.clone(),
+      retention: self.retention.clone(),
+      last_compaction: self.last_compaction,
+    }
+  }
+}
+
+impl Debug for Scheduler {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Scheduler")
+      .field("state", &self.state)
+      .field("ledger", &self.ledger)
+      .field("stores", &self.stores)
+      .field("executors", &self.executors)
+      .field("logger", &self.logger)
+      .field("clock", &"<clock>")
+      .field("dirty", &self.dirty)
+      .field("queue", &self.queue.len())
+      .field("cancellations", &self.cancellations)
+      .field("metrics", &self.metrics)
+      .field("retention", &self.retention)
+      .field("last_compaction", &self.last_compaction)
+      .finish()
+  }
 }
 
 #[async_trait]
 #[typetag::serde]
 impl Schedule for Scheduler {
   fn startup(&mut self) {
-    println!(
-      "{}",
-      "::::   Scheduler Starting Up   ::::"
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green()
-    );
+    if let Some(logger) = &self.logger {
+      logger.logo();
+    }
+    Scheduler::banner(&self.logger, String::from("Scheduler Starting Up"));
     self.state = SchedulerState::Running;
   }
 
+  async fn shutdown(&mut self) {
+    let now = self.clock.now();
+    for (key, store) in self.stores.iter_mut() {
+      for job in store.jobs.values_mut() {
+        let _ = Scheduler::teardown_job(
+          job,
+          &mut self.ledger,
+          &self.logger,
+          key,
+          now,
+        )
+        .await;
+      }
+    }
+    for (alias, executor) in self.executors.iter() {
+      match executor.teardown() {
+        Ok(_) => Scheduler::banner(
+          &self.logger,
+          format!("Tearing Down Executor {}", alias),
+        ),
+        Err(e) => {
+          if let Some(logger) = &self.logger {
+            logger.err(e)
+          }
+        }
+      }
+    }
+    self.state = SchedulerState::Stopped;
+    self.dirty = true;
+  }
+
+  // Handles a message and logs how it went. Messages wrapped in Msg::Reply are
+  // answered with the result as well.
   async fn proxy(
     &mut self,
     msg: Msg,
     _sender: &Sender<Msg>,
     _reader: &Receiver<Msg>,
   ) {
-    match self.logger.clone() {
-      Some(logger) => match msg {
-        Msg::LoadFromDisk => self.load_snapshot_from_disk(),
-        Msg::LoadFromSnapshot(snap) => self.load_snapshot_from_mem(snap),
-        Msg::Snapshot => self.save_snapshot(),
-        Msg::AddExecutor(alias, exctr) => match self
-          .add_executor(alias.clone(), exctr)
-        {
-          Ok(_) => logger.info(format!("ADDING EXECUTER {} SUCCEEDED", &alias)),
-          Err(e) => logger.err(format!("{}", e)),
-        },
-        Msg::RemoveExecutor(alias) => match self.remove_executor(&alias) {
-          Ok(_) => {
-            logger.info(format!("REMOVING EXECUTOR {} SUCCEEDED", &alias))
-          }
-          Err(e) => logger.err(format!("{}", e)),
-        },
-        Msg::AddStore(alias, store) => {
-          match self.add_store(alias.clone(), store).await {
-            Ok(_) => logger.info(format!("ADDING STORE {} SUCCEEDED", &alias)),
-            Err(e) => logger.err(format!("{}", e)),
-          }
-        }
-        // TODO: Implement Modify Store
-        // Msg::ModifyStore(alias, properties) => scheduler.modify_store(alias, properties),
-        Msg::RemoveStore(alias) => match self.remove_store(&alias) {
-          Ok(_) => logger.info(format!("REMOVING STORE {} SUCCEEDED", &alias)),
-          Err(e) => logger.err(format!("{}", e)),
-        },
-        Msg::AddJob(
-          alias,
-          store_alias,
-          executor,
-          start_time,
-          end_time,
-          job,
-        ) => {
-          match self.add_job(
+    let (msg, reply) = match msg {
+      Msg::Read(read, reply) => {
+        let _ = reply.send(self.read(read)).await;
+        return;
+      }
+      Msg::Reply(msg, reply) => (*msg, Some(reply)),
+      msg => (msg, None),
+    };
+    let result = match msg {
+      Msg::LoadFromDisk => {
+        self.load_snapshot_from_disk().await;
+        Ok(None)
+      }
+      Msg::LoadFromSnapshot(snap) => {
+        self.load_snapshot_from_mem(snap).await;
+        Ok(None)
+      }
+      Msg::Snapshot => {
+        self.save_snapshot();
+        Ok(None)
+      }
+      Msg::Shutdown => {
+        self.shutdown().await;
+        Ok(Some(String::from("SHUTTING DOWN SCHEDULER SUCCEEDED")))
+      }
+      Msg::AddExecutor(alias, exctr) => self
+        .add_executor(alias.clone(), exctr)
+        .await
+        .map(|_| Some(format!("ADDING EXECUTER {} SUCCEEDED", &alias))),
+      Msg::RemoveExecutor(alias) => self
+        .remove_executor(&alias)
+        .await
+        .map(|_| Some(format!("REMOVING EXECUTOR {} SUCCEEDED", &alias))),
+      Msg::AddStore(alias, store) => self
+        .add_store(alias.clone(), store)
+        .await
+        .map(|_| Some(format!("ADDING STORE {} SUCCEEDED", &alias))),
+      // TODO: Implement Modify Store
+      // Msg::ModifyStore(alias, properties) => scheduler.modify_store(alias, properties),
+      Msg::RemoveStore(alias) => self
+        .remove_store(&alias)
+        .await
+        .map(|_| Some(format!("REMOVING STORE {} SUCCEEDED", &alias))),
+      Msg::AddJob(alias, store_alias, executor, start_time, end_time, job) => {
+        self
+          .add_job(
             alias.clone(),
             store_alias.clone(),
             executor,
             start_time,
             end_time,
             job,
-          ) {
-            Ok(_) => logger.info(format!(
+          )
+          .await
+          .map(|_| {
+            Some(format!(
               "ADDING JOB {} TO STORE {} SUCCEEDED",
               &alias, &store_alias
-            )),
-            Err(e) => logger.err(format!("{}", e)),
-          }
-        }
-        // TODO: Implement ModifyJob
-        Msg::ModifyJob(alias, store_alias) => {
-          match self.modify_job(alias.clone(), store_alias.clone()) {
-            Ok(_) => logger.info(format!(
-              "MODIFYING JOB {} IN STORE {} SUCCEEDED",
-              &alias, &store_alias
-            )),
-            Err(e) => logger.err(format!("{}", e)),
-          }
-        }
-        Msg::RemoveJob(alias, store_alias) => {
-          match self.remove_job(alias.clone(), store_alias.clone()) {
-            Ok(_) => logger.info(format!(
-              "REMOVING JOB {} FROM STORE {} SUCCEEDED",
-              &alias, &store_alias
-            )),
-            Err(e) => logger.err(format!("{}", e)),
-          }
-        }
-        // TODO: Implement Pause Job
-        Msg::PauseJob(alias, store_alias) => {
-          match self.pause_job(alias.clone(), store_alias.clone()) {
-            Ok(_) => logger.info(format!(
-              "PAUSING JOB {} IN STORE {} SUCCEEDED",
-              &alias, &store_alias
-            )),
-            Err(e) => logger.err(format!("{}", e)),
-          }
-        }
-        // TODO: Implement Resume Job
-        Msg::ResumeJob(alias, store_alias) => {
-          match self.resume_job(alias.clone(), store_alias.clone()) {
-            Ok(_) => logger.info(format!(
-              "RESUMING JOB {} IN STORE {} SUCCEEDED",
-              &alias, &store_alias,
-            )),
-            Err(e) => logger.err(e),
-          }
-        }
-        Msg::Log(id, _status, _result) => logger.info(format!("LOG {}", id)),
-      },
-      None => (),
+            ))
+          })
+      }
+      Msg::ModifyJob(
+        alias,
+        store_alias,
+        executor,
+        start_time,
+        end_time,
+        job,
+      ) => self
+        .modify_job(
+          alias.clone(),
+          store_alias.clone(),
+          executor,
+          start_time,
+          end_time,
+          job,
+        )
+        .await
+        .map(|_| {
+          Some(format!(
+            "MODIFYING JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias
+          ))
+        }),
+      Msg::RemoveJob(alias, store_alias) => self
+        .remove_job(alias.clone(), store_alias.clone())
+        .await
+        .map(|_| {
+          Some(format!(
+            "REMOVING JOB {} FROM STORE {} SUCCEEDED",
+            &alias, &store_alias
+          ))
+        }),
+      // TODO: Implement Pause Job
+      Msg::PauseJob(alias, store_alias) => self
+        .pause_job(alias.clone(), store_alias.clone())
+        .map(|_| {
+          Some(format!(
+            "PAUSING JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias
+          ))
+        }),
+      // TODO: Implement Resume Job
+      Msg::ResumeJob(alias, store_alias) => self
+        .resume_job(alias.clone(), store_alias.clone())
+        .map(|_| {
+          Some(format!(
+            "RESUMING JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::SetMisfire(alias, store_alias, misfire) => self
+        .set_misfire(alias.clone(), store_alias.clone(), misfire)
+        .map(|_| {
+          Some(format!(
+            "SETTING MISFIRE FOR JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::SetTimeout(alias, store_alias, timeout) => self
+        .set_timeout(alias.clone(), store_alias.clone(), timeout)
+        .map(|_| {
+          Some(format!(
+            "SETTING TIMEOUT FOR JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::SetRetention(retention) => self
+        .set_retention(retention)
+        .map(|_| Some(String::from("SETTING RETENTION SUCCEEDED"))),
+      Msg::AddTrigger(alias, store_alias, trigger) => {
+        let trigger_alias = trigger.alias.clone();
+        self
+          .add_trigger(alias.clone(), store_alias.clone(), trigger)
+          .map(|_| {
+            Some(format!(
+              "ADDING TRIGGER {} TO JOB {} IN STORE {} SUCCEEDED",
+              &trigger_alias, &alias, &store_alias,
+            ))
+          })
+      }
+      Msg::ModifyTrigger(alias, store_alias, trigger) => {
+        let trigger_alias = trigger.alias.clone();
+        self
+          .modify_trigger(alias.clone(), store_alias.clone(), trigger)
+          .map(|_| {
+            Some(format!(
+              "MODIFYING TRIGGER {} OF JOB {} IN STORE {} SUCCEEDED",
+              &trigger_alias, &alias, &store_alias,
+            ))
+          })
+      }
+      Msg::RemoveTrigger(alias, store_alias, trigger_alias) => self
+        .remove_trigger(
+          alias.clone(),
+          store_alias.clone(),
+          trigger_alias.clone(),
+        )
+        .map(|_| {
+          Some(format!(
+            "REMOVING TRIGGER {} FROM JOB {} IN STORE {} SUCCEEDED",
+            &trigger_alias, &alias, &store_alias,
+          ))
+        }),
+      Msg::CancelRun(store_alias, alias) => self
+        .cancel_run(store_alias.clone(), alias.clone())
+        .map(|_| {
+          Some(format!(
+            "CANCELLING RUN OF JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::RetryRun(store_alias, alias, run_id) => self
+        .retry_run(store_alias.clone(), alias.clone(), run_id)
+        .await
+        .map(|_| {
+          Some(format!(
+            "RETRYING RUN OF JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::RunJob(store_alias, alias) => self
+        .run_job(store_alias.clone(), alias.clone())
+        .await
+        .map(|_| {
+          Some(format!(
+            "RUNNING JOB {} IN STORE {} SUCCEEDED",
+            &alias, &store_alias,
+          ))
+        }),
+      Msg::Log(id, _status, _result) => Ok(Some(format!("LOG {}", id))),
+      Msg::Read(..) | Msg::Reply(..) => {
+        Err(String::from("Requests can't be nested in a reply"))
+      }
     };
+    if let Some(logger) = &self.logger {
+      match &result {
+        Ok(Some(line)) => logger.info(line.clone()),
+        Ok(None) => (),
+        Err(e) => logger.err(e.clone()),
+      }
+    }
+    if let Some(reply) = reply {
+      let _ = reply.send(result.map(|_| ())).await;
+    }
   }
 
   async fn check_jobs(&mut self) {
-    for (_key, value) in &mut self.stores {
-      let cpy = &mut value.clone();
-      match cpy.get_due_jobs() {
-        Ok(ready) => {
-          for to_execute in ready {
-            let executioner = self.executors.get(&to_execute.executor);
-            match executioner {
-              None => (),
-              Some(e) => {
-                let (should_run, next) =
-                  to_execute.validate_triggers(&mut self.ledger).await;
-                if (should_run) {
-                  match (e.execute(&to_execute.job).await) {
-                    Ok(_v) => {
-                      if let Some(logger) = &self.logger {
-                        logger.info(format!(
-                          "EXECUTING JOB {} FROM STORE {} SUCCEEDED",
-                          &to_execute.alias,
-                          &value.clone().alias
-                        ))
-                      }
-                    }
-                    Err(e) => {
-                      if let Some(logger) = &self.logger {
-                        logger.err(e)
-                      }
-                    }
-                  };
+    if self.state == SchedulerState::Stopped {
+      return;
+    }
+    let due = self.take_due(self.clock.now());
+    for (key, job_alias) in due {
+      let value = match self.stores.get_mut(&key) {
+        Some(v) => v,
+        None => continue,
+      };
+      let name = value.alias.clone();
+      let mut requeue = None;
+      let mut finished = false;
+      match value.jobs.get_mut(&job_alias) {
+        // Paused jobs leave the queue, resuming them queues them again.
+        Some(to_execute) if to_execute.state == Status::Paused => {}
+        Some(to_execute) if to_execute.start_time <= self.clock.now() => {
+          let executioner = self.executors.get(&to_execute.executor);
+          match executioner {
+            None => requeue = Some(self.clock.now() + RECHECK_INTERVAL),
+            Some(e) => {
+              let now = self.clock.now();
+              let scheduled = to_execute.start_time;
+              let span =
+                trace::job(&to_execute.alias, &key, &to_execute.executor);
+              let late = match &to_execute.misfire {
+                Some(misfire) => now - to_execute.start_time > misfire.grace,
+                None => false,
+              };
+              let (runs, missed, next) = match to_execute.misfire.clone() {
+                Some(misfire) if late => {
+                  Scheduler::catch_up(
+                    to_execute,
+                    &misfire,
+                    &self.ledger,
+                    &key,
+                    now,
+                  )
+                  .instrument(trace::triggers(&span))
+                  .await
+                }
+                _ => {
+                  let ctx = TriggerContext::new(
+                    now,
+                    &self.ledger,
+                    self.ledger.alias.clone(),
+                    key.clone(),
+                    to_execute.alias.clone(),
+                    to_execute.last_run,
+                    to_execute.last_status.clone(),
+                  );
+                  let (should_run, next) = to_execute
+                    .validate_triggers(&ctx)
+                    .instrument(trace::triggers(&span))
+                    .await;
+                  let runs =
+                    if should_run { vec![scheduled] } else { vec![] };
+                  (runs, vec![], next)
                 }
+              };
 
-                if let Some(v) = next {
-                  if (should_run) {
+              for time in missed {
+                self.ledger.ledger.insert(
+                  &key,
+                  &to_execute.alias,
+                  &Status::Missed,
+                  &time,
+                );
+                if let Some(logger) = &self.logger {
+                  logger.warn(format!(
+                    "JOB {} FROM STORE {} MISSED A RUN AT {}",
+                    &to_execute.alias, &name, time
+                  ))
+                }
+                self.dirty = true;
+              }
+
+              for &scheduled in &runs {
+                let run = trace::run(&span, &to_execute.alias, scheduled);
+                let handles = Handles {
+                  ledger: &mut self.ledger,
+                  cancellations: &self.cancellations,
+                  metrics: &self.metrics,
+                  logger: &self.logger,
+                  clock: &*self.clock,
+                };
+                Scheduler::execute(
+                  handles,
+                  e,
+                  to_execute,
+                  &key,
+                  &name,
+                  scheduled,
+                  None,
+                )
+                .instrument(run)
+                .await;
+                self.dirty = true;
+              }
+
+              match next {
+                Some(v) => {
+                  if !runs.is_empty() || late {
                     to_execute.start_time = v;
                     self.dirty = true;
+                    requeue = Some(v);
+                  } else {
+                    requeue = Some(now + RECHECK_INTERVAL);
                   }
-                } else {
-                  match value.remove_job(&to_execute.alias) {
-                    Ok(_v) => {
-                      if let Some(logger) = &self.logger {
-                        logger.info(format!(
-                          "REMOVING JOB {} FROM STORE {} SUCCEEDED",
-                          &to_execute.alias,
-                          &value.clone().alias
-                        ));
-                        self.dirty = true;
-                      }
-                    }
-                    Err(e) => {
-                      if let Some(logger) = &self.logger {
-                        logger.err(e);
-                        self.dirty = true;
-                      }
-                    }
-                  };
                 }
+                None => finished = true,
               }
-            };
-          }
+            }
+          };
         }
-        Err(_e) => println!(
-          "Failed to get jobs that are ready to execute for Store {}",
-          &cpy.alias
-        ),
+        _ => (),
+      }
+
+      if let Some(time) = requeue {
+        self.enqueue(time, &key, &job_alias);
+      }
+
+      if finished {
+        let now = self.clock.now();
+        let removed = match self.stores.get_mut(&key) {
+          Some(value) => match value.jobs.get_mut(&job_alias) {
+            // A failed teardown is recorded on its own, the job is removed
+            // either way so it doesn't sit dormant in the store.
+            Some(job) => {
+              let _ = Scheduler::teardown_job(
+                job,
+                &mut self.ledger,
+                &self.logger,
+                &key,
+                now,
+              )
+              .await;
+              value.remove_job(&job_alias)
+            }
+            None => value.remove_job(&job_alias),
+          },
+          None => Err(format!("Store {} was not found in stores", &key)),
+        };
+        match removed {
+          Ok(_v) => {
+            if let Some(logger) = &self.logger {
+              logger.info(format!(
+                "REMOVING JOB {} FROM STORE {} SUCCEEDED",
+                &job_alias, &name
+              ));
+            }
+          }
+          Err(e) => {
+            if let Some(logger) = &self.logger {
+              logger.err(e);
+            }
+          }
+        };
+        self.dirty = true;
       }
     }
+    self.count_jobs();
+    self.compact_ledger(self.clock.now());
+  }
+
+  fn next_wakeup(&self) -> Option<i64> {
+    self.queue.peek().map(|Reverse((time, _, _))| *time)
+  }
+
+  fn now(&self) -> i64 {
+    self.clock.now()
   }
 
   fn is_dirty(&self) -> bool {
@@ -252,6 +960,12 @@ impl Schedule for Scheduler {
         Entry::Vacant(entry) => {
           self.dirty = true;
           entry.insert(store);
+          self.enqueue_store(&alias);
+          self.count_jobs();
+          Scheduler::banner(
+            &self.logger,
+            format!("Starting JobStore {}", &alias),
+          );
           Ok(())
         }
       },
@@ -259,7 +973,9 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn add_job(
+  // Jobs whose executor is running are started right away, a job that fails
+  // to start isn't added and leaves no history behind.
+  async fn add_job(
     &mut self,
     alias: String,
     store_alias: String,
@@ -272,7 +988,21 @@ impl Schedule for Scheduler {
       Entry::Occupied(mut entry) => {
         let store = entry.get_mut();
         self.dirty = true;
-        store.add_job(alias, executor, start_time, end_time, job)
+        store.add_job(alias.clone(), executor, start_time, end_time, job)?;
+        if let Some(job) = store.jobs.get_mut(&alias) {
+          if self.executors.contains_key(&job.executor) {
+            if let Err(e) = Scheduler::startup_job(job, &self.logger).await {
+              store.jobs.remove(&alias);
+              return Err(e);
+            }
+          }
+        }
+        self.metrics.set_jobs(&store_alias, store.jobs.len());
+        let time = store.jobs.get(&alias).map(|job| job.start_time);
+        if let Some(time) = time {
+          self.enqueue(time, &store_alias, &alias);
+        }
+        Ok(())
       }
       Entry::Vacant(_entry) => {
         Err(format!("Store {} is not found in stores", &store_alias))
@@ -280,7 +1010,9 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn add_executor(
+  // Starts the jobs already waiting on the executor, the ones that fail stay
+  // stopped and are started again before their next run.
+  async fn add_executor(
     &mut self,
     alias: String,
     executor: Executor,
@@ -297,6 +1029,25 @@ impl Schedule for Scheduler {
         Entry::Vacant(entry) => {
           entry.insert(executor);
           self.dirty = true;
+          Scheduler::banner(
+            &self.logger,
+            format!("Starting Executor {}", &alias),
+          );
+          let now = self.clock.now();
+          for (key, store) in self.stores.iter_mut() {
+            for job in store.jobs.values_mut() {
+              if job.executor == alias {
+                let _ = Scheduler::start_job(
+                  job,
+                  &mut self.ledger,
+                  &self.logger,
+                  key,
+                  now,
+                )
+                .await;
+              }
+            }
+          }
           Ok(())
         }
       },
@@ -304,13 +1055,29 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn remove_store(&mut self, alias: &String) -> Result<(), String> {
+  async fn remove_store(&mut self, alias: &String) -> Result<(), String> {
+    let now = self.clock.now();
     match self.stores.entry(alias.clone()) {
       Entry::Occupied(mut entry) => {
         let store = entry.get_mut();
+        for job in store.jobs.values_mut() {
+          let _ = Scheduler::teardown_job(
+            job,
+            &mut self.ledger,
+            &self.logger,
+            alias,
+            now,
+          )
+          .await;
+        }
         match store.teardown() {
           Ok(_) => match self.stores.remove(alias) {
             Some(_) => {
+              Scheduler::banner(
+                &self.logger,
+                format!("Tearing Down JobStore {}", alias),
+              );
+              self.metrics.remove_store(alias);
               self.dirty = true;
               Ok(())
             }
@@ -325,16 +1092,35 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn modify_job(
+  // The old work is torn down first, the job is left as it was when that
+  // fails. The new work is started before its next run.
+  async fn modify_job(
     &mut self,
     alias: String,
     store_alias: String,
+    executor: String,
+    start_time: i64,
+    end_time: Option<i64>,
+    job: Box<dyn Work>,
   ) -> Result<(), String> {
+    let now = self.clock.now();
     match self.stores.entry(store_alias.clone()) {
       Entry::Occupied(mut entry) => {
         let store = entry.get_mut();
+        if let Some(job) = store.jobs.get_mut(&alias) {
+          Scheduler::teardown_job(
+            job,
+            &mut self.ledger,
+            &self.logger,
+            &store_alias,
+            now,
+          )
+          .await?;
+        }
+        store.modify_job(alias.clone(), executor, start_time, end_time, job)?;
         self.dirty = true;
-        store.modify_job(&alias)
+        self.enqueue(start_time, &store_alias, &alias);
+        Ok(())
       }
       Entry::Vacant(_entry) => {
         Err(format!("Store {} was not found in stores", &store_alias))
@@ -368,7 +1154,118 @@ impl Schedule for Scheduler {
       Entry::Occupied(mut entry) => {
         let store = entry.get_mut();
         self.dirty = true;
-        store.resume_job(alias)
+        store.resume_job(alias.clone())?;
+        if let Some(job) = store.jobs.get(&alias) {
+          let start_time = job.start_time;
+          self.enqueue(start_time, &store_alias, &alias);
+        }
+        Ok(())
+      }
+      Entry::Vacant(_entry) => {
+        Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    }
+  }
+
+  fn set_misfire(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    misfire: Option<Misfire>,
+  ) -> Result<(), String> {
+    match self.stores.entry(store_alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let store = entry.get_mut();
+        self.dirty = true;
+        store.set_misfire(alias, misfire)
+      }
+      Entry::Vacant(_entry) => {
+        Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    }
+  }
+
+  fn set_timeout(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    timeout: Option<i64>,
+  ) -> Result<(), String> {
+    match self.stores.entry(store_alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let store = entry.get_mut();
+        self.dirty = true;
+        store.set_timeout(alias, timeout)
+      }
+      Entry::Vacant(_entry) => {
+        Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    }
+  }
+
+  fn set_retention(
+    &mut self,
+    retention: Option<Retention>,
+  ) -> Result<(), String> {
+    self.retention = retention;
+    self.dirty = true;
+    Ok(())
+  }
+
+  fn cancel_run(
+    &mut self,
+    store_alias: String,
+    alias: String,
+  ) -> Result<(), String> {
+    self.cancellations.cancel(&store_alias, &alias)
+  }
+
+  async fn retry_run(
+    &mut self,
+    store_alias: String,
+    alias: String,
+    run_id: String,
+  ) -> Result<(), String> {
+    self.run_now(store_alias, alias, Some(run_id)).await
+  }
+
+  async fn run_job(
+    &mut self,
+    store_alias: String,
+    alias: String,
+  ) -> Result<(), String> {
+    self.run_now(store_alias, alias, None).await
+  }
+
+  fn add_trigger(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String> {
+    match self.stores.entry(store_alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let store = entry.get_mut();
+        self.dirty = true;
+        store.add_trigger(alias, trigger)
+      }
+      Entry::Vacant(_entry) => {
+        Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    }
+  }
+
+  fn modify_trigger(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String> {
+    match self.stores.entry(store_alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let store = entry.get_mut();
+        self.dirty = true;
+        store.modify_trigger(alias, trigger)
       }
       Entry::Vacant(_entry) => {
         Err(format!("Store {} was not found in stores", &store_alias))
@@ -376,16 +1273,17 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn remove_job(
+  fn remove_trigger(
     &mut self,
     alias: String,
     store_alias: String,
+    trigger_alias: String,
   ) -> Result<(), String> {
     match self.stores.entry(store_alias.clone()) {
       Entry::Occupied(mut entry) => {
         let store = entry.get_mut();
         self.dirty = true;
-        store.remove_job(&alias)
+        store.remove_trigger(alias, trigger_alias)
       }
       Entry::Vacant(_entry) => {
         Err(format!("Store {} was not found in stores", &store_alias))
@@ -393,13 +1291,125 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn remove_executor(&mut self, alias: &String) -> Result<(), String> {
+  // Maps are keyed by alias and sorted, so answers are stable.
+  fn read(&self, read: Read) -> Result<Value, String> {
+    let store = |alias: &String| {
+      self
+        .stores
+        .get(alias)
+        .ok_or(format!("Store {} was not found in stores", alias))
+    };
+    let job = |store_alias: &String, alias: &String| {
+      store(store_alias)?.jobs.get(alias).ok_or(format!(
+        "Job {} was not found in the Store {}",
+        alias, store_alias
+      ))
+    };
+    let value = match read {
+      Read::Health => Ok(json!({
+        "state": self.state,
+        "now": self.clock.now(),
+        "stores": self.stores.len(),
+        "executors": self.executors.len(),
+      })),
+      Read::Stores => {
+        let stores: BTreeMap<&String, BTreeSet<&String>> = self
+          .stores
+          .iter()
+          .map(|(alias, store)| (alias, store.jobs.keys().collect()))
+          .collect();
+        serde_json::to_value(stores)
+      }
+      Read::Executors => {
+        let executors: BTreeMap<&String, &Executor> =
+          self.executors.iter().collect();
+        serde_json::to_value(executors)
+      }
+      Read::Jobs(store_alias) => {
+        let jobs: BTreeMap<&String, &Job> =
+          store(&store_alias)?.jobs.iter().collect();
+        serde_json::to_value(jobs)
+      }
+      Read::Job(store_alias, alias) => {
+        serde_json::to_value(job(&store_alias, &alias)?)
+      }
+      Read::Triggers(store_alias, alias) => {
+        let triggers: BTreeMap<&String, &Box<Trigger>> =
+          job(&store_alias, &alias)?.triggers.iter().collect();
+        serde_json::to_value(triggers)
+      }
+      Read::Runs(query) => {
+        serde_json::to_value(self.ledger.ledger.query(&query))
+      }
+    };
+    value.map_err(|e| format!("{}", e))
+  }
+
+  fn cancellations(&self) -> Cancellations {
+    self.cancellations.clone()
+  }
+
+  fn metrics(&self) -> Metrics {
+    self.metrics.clone()
+  }
+
+  // A job that fails to tear down is kept.
+  async fn remove_job(
+    &mut self,
+    alias: String,
+    store_alias: String,
+  ) -> Result<(), String> {
+    let now = self.clock.now();
+    match self.stores.entry(store_alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let store = entry.get_mut();
+        if let Some(job) = store.jobs.get_mut(&alias) {
+          Scheduler::teardown_job(
+            job,
+            &mut self.ledger,
+            &self.logger,
+            &store_alias,
+            now,
+          )
+          .await?;
+        }
+        self.dirty = true;
+        let removed = store.remove_job(&alias);
+        self.metrics.set_jobs(&store_alias, store.jobs.len());
+        removed
+      }
+      Entry::Vacant(_entry) => {
+        Err(format!("Store {} was not found in stores", &store_alias))
+      }
+    }
+  }
+
+  async fn remove_executor(&mut self, alias: &String) -> Result<(), String> {
+    let now = self.clock.now();
     match self.executors.entry(alias.clone()) {
       Entry::Occupied(mut entry) => {
+        for (key, store) in self.stores.iter_mut() {
+          for job in store.jobs.values_mut() {
+            if &job.executor == alias {
+              let _ = Scheduler::teardown_job(
+                job,
+                &mut self.ledger,
+                &self.logger,
+                key,
+                now,
+              )
+              .await;
+            }
+          }
+        }
         let exctr = entry.get_mut();
         match exctr.teardown() {
           Ok(_) => match self.executors.remove(alias) {
             Some(_v) => {
+              Scheduler::banner(
+                &self.logger,
+                format!("Tearing Down Executor {}", alias),
+              );
               self.dirty = true;
               Ok(())
             }
@@ -423,24 +1433,22 @@ impl Schedule for Scheduler {
   }
 
   fn save_snapshot(&mut self) {
+    if let Some(logger) = &self.logger {
+      logger.debug(String::from("SAVING SNAPSHOT"))
+    }
     let snap = self.create_snapshot();
     let db = sled::open("./horo").unwrap();
     db.insert("scope", snap).unwrap();
   }
 
-  fn load_snapshot_from_disk(&mut self) {
+  async fn load_snapshot_from_disk(&mut self) {
     let db = sled::open("./horo").unwrap();
     let disk_snap = db.get("scope").unwrap();
     match disk_snap {
       Some(snap) => {
         let schdlr: bincode::Result<Scheduler> = bincode::deserialize(&snap);
         match schdlr {
-          Ok(v) => {
-            self.stores = v.stores;
-            self.ledger = v.ledger;
-            self.executors = v.executors;
-            self.logger = v.logger;
-          }
+          Ok(v) => self.restore(v).await,
           Err(e) => match self.logger.clone() {
             Some(logger) => logger.err(format!("{}", e)),
             None => {}
@@ -451,15 +1459,10 @@ impl Schedule for Scheduler {
     }
   }
 
-  fn load_snapshot_from_mem(&mut self, snapshot: Vec<u8>) {
+  async fn load_snapshot_from_mem(&mut self, snapshot: Vec<u8>) {
     let schdlr: bincode::Result<Scheduler> = bincode::deserialize(&snapshot);
     match schdlr {
-      Ok(v) => {
-        self.stores = v.stores;
-        self.ledger = v.ledger;
-        self.executors = v.executors;
-        self.logger = v.logger;
-      }
+      Ok(v) => self.restore(v).await,
       Err(_) => {}
     }
   }
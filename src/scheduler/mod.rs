@@ -2,19 +2,22 @@ pub mod blocking;
 
 use async_channel;
 use async_channel::{Receiver, Sender};
-use async_std::prelude::*;
-use async_std::stream;
+use async_std::net::TcpListener;
 use async_std::task;
 use async_trait::async_trait;
-use chrono::prelude::*;
-use futures::{select, FutureExt};
+use futures::{future, select, FutureExt};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fmt;
 use std::time::Duration;
 
-use crate::executor::Executor;
-use crate::job::Work;
+use crate::admin;
+use crate::executor::{Cancellations, Executor};
+use crate::job::{Misfire, Work};
+use crate::ledger::{Query, Retention};
+use crate::metrics::{self, Metrics};
 use crate::store::Store;
+use crate::trigger::Trigger;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum SchedulerState {
@@ -33,6 +36,19 @@ impl fmt::Display for SchedulerState {
   }
 }
 
+/// What can be read from a running scheduler, answered as JSON. Jobs and
+/// triggers are addressed as (store, job).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Read {
+  Health,
+  Stores,
+  Executors,
+  Jobs(String),
+  Job(String, String),
+  Triggers(String, String),
+  Runs(Query),
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum Msg {
   // Scheduler Messages
@@ -40,6 +56,7 @@ pub enum Msg {
   LoadFromDisk,
   LoadFromSnapshot(Vec<u8>),
   Snapshot,
+  Shutdown,
   // Executor Msgs:
   AddExecutor(String, Executor),
   RemoveExecutor(String),
@@ -51,10 +68,24 @@ pub enum Msg {
 
   // Job Msgs
   AddJob(String, String, String, i64, Option<i64>, Box<dyn Work>),
-  ModifyJob(String, String),
+  // Replaces a job's work and schedule, keeping its triggers.
+  ModifyJob(String, String, String, i64, Option<i64>, Box<dyn Work>),
   RemoveJob(String, String),
   PauseJob(String, String),
   ResumeJob(String, String),
+  SetMisfire(String, String, Option<Misfire>),
+  SetTimeout(String, String, Option<i64>),
+  SetRetention(Option<Retention>),
+  AddTrigger(String, String, Trigger),
+  ModifyTrigger(String, String, Trigger),
+  RemoveTrigger(String, String, String),
+  // Cancels the in-flight run of a job, addressed as (store, job).
+  CancelRun(String, String),
+  // Runs a job again right away, resuming the given workflow run, addressed
+  // as (store, job, run id).
+  RetryRun(String, String, String),
+  // Runs a job right away, outside of its schedule, addressed as (store, job).
+  RunJob(String, String),
 
   // Listener Msgs
   // AddListener(String, String, String),
@@ -64,52 +95,145 @@ pub enum Msg {
   // ------------------------------------------------------------------------
   // Common:
   Log(String, String, String),
+
+  // Requests expecting an answer on the given channel, they can't be
+  // serialized.
+  #[serde(skip)]
+  Read(Read, Sender<Result<Value, String>>),
+  // Handles the wrapped message and answers with how it went.
+  #[serde(skip)]
+  Reply(Box<Msg>, Sender<Result<(), String>>),
+}
+
+/// How `daemon` runs a scheduler. With `save_state` a snapshot is saved
+/// whenever the scheduler changes. When `metrics` holds an address, the
+/// scheduler's metrics are served there in the Prometheus text format at
+/// `/metrics`, and when `admin` holds one, the admin API is served there.
+///
+/// The admin API can run any command on this machine, it requires
+/// `admin_token` as a bearer token and should only listen on loopback, such
+/// as `127.0.0.1:8080`, unless it's behind a proxy that adds TLS.
+#[derive(Clone, Debug, Default)]
+pub struct DaemonOptions {
+  pub save_state: bool,
+  pub metrics: Option<String>,
+  pub admin: Option<String>,
+  pub admin_token: Option<String>,
 }
 
-pub fn get_elapsed_time(start_time: i64) {
-  let now = Utc::now().timestamp_nanos();
-  println!("{}", now - start_time * 1000000);
+fn bind(
+  name: &str,
+  addr: &Option<String>,
+) -> Result<Option<TcpListener>, String> {
+  match addr {
+    Some(addr) => task::block_on(TcpListener::bind(addr))
+      .map(Some)
+      .map_err(|e| {
+        format!("Unable to bind {} listener on {}: {}", name, addr, e)
+      }),
+    None => Ok(None),
+  }
 }
 
+// Runs the scheduler in the background, failing when a listener in `options`
+// can't be bound.
 pub fn daemon(
   scheduler: Box<dyn Schedule>,
-  save_state: bool,
-) -> (Sender<Msg>, Receiver<Msg>) {
+  options: DaemonOptions,
+) -> Result<(Sender<Msg>, Receiver<Msg>), String> {
+  let admin_token = match (&options.admin, &options.admin_token) {
+    (Some(_), None) => {
+      return Err(String::from("Serving the admin API requires an admin token"))
+    }
+    (Some(_), Some(token)) if token.is_empty() => {
+      return Err(String::from("The admin token can't be empty"))
+    }
+    (_, token) => token.clone().unwrap_or_default(),
+  };
+  let metrics_listener = bind("metrics", &options.metrics)?;
+  let admin_listener = bind("admin", &options.admin)?;
+  let save_state = options.save_state;
   let mut schdlr = scheduler;
   let (s, r) = async_channel::unbounded();
   let (s_cpy, r_cpy) = (s.clone(), r.clone());
-  let mut interval = stream::interval(Duration::from_millis(5));
+
+  if let Some(listener) = metrics_listener {
+    task::spawn(metrics::serve(listener, schdlr.metrics()));
+  }
+
+  if let Some(listener) = admin_listener {
+    task::spawn(admin::serve(listener, s.clone(), admin_token));
+  }
+
+  // The loop below is busy while jobs execute, so cancellations are picked off
+  // the channel here and everything else is forwarded to it.
+  let (fwd_sender, fwd_reader) = async_channel::unbounded();
+  let cancellations = schdlr.cancellations();
+  task::spawn(async move {
+    while let Ok(msg) = r_cpy.recv().await {
+      let forwarded = match msg {
+        Msg::CancelRun(store, job) => {
+          match cancellations.cancel(&store, &job) {
+            Ok(_) => Ok(()),
+            Err(_) => fwd_sender.send(Msg::CancelRun(store, job)).await,
+          }
+        }
+        Msg::Reply(msg, reply) => match *msg {
+          Msg::CancelRun(store, job) => {
+            match cancellations.cancel(&store, &job) {
+              Ok(_) => {
+                let _ = reply.send(Ok(())).await;
+                Ok(())
+              }
+              Err(_) => {
+                let msg = Box::new(Msg::CancelRun(store, job));
+                fwd_sender.send(Msg::Reply(msg, reply)).await
+              }
+            }
+          }
+          msg => fwd_sender.send(Msg::Reply(Box::new(msg), reply)).await,
+        },
+        msg => fwd_sender.send(msg).await,
+      };
+      if forwarded.is_err() {
+        break;
+      }
+    }
+  });
 
   task::spawn(async move {
-    let (sender, reader) = (s_cpy, r_cpy);
+    let (sender, reader) = (s_cpy, fwd_reader);
     schdlr.startup();
     loop {
+      // Sleep until the earliest job is due, or forever when there is nothing
+      // queued; any message wakes the loop up and the timer is recomputed.
+      let timer = match schdlr.next_wakeup() {
+        Some(wake) => {
+          let wait = (wake - schdlr.now()).max(0) as u64;
+          task::sleep(Duration::from_nanos(wait)).boxed()
+        }
+        None => future::pending().boxed(),
+      };
+
       select! {
           m = reader.recv().fuse() => {
               match m {
                   Ok(msg) => schdlr.proxy(msg, &sender, &reader).await,
-                  Err(e) => println!("{}", e)
+                  Err(_) => break
               }
           },
-          i = interval.next().fuse() => {
-              match i {
-                  Some(_) => {
-                    schdlr.check_jobs().await;
-                    if (schdlr.is_dirty()) {
-                      if (save_state){
-                        println!("Saving snapshot");
-                        schdlr.save_snapshot();
-                      }
-                      schdlr.set_dirty(false);
-                    }
-                  },
-                  None => println!("Nothing in interval hit")
-              }
-          }
+          _ = timer.fuse() => schdlr.check_jobs().await
       };
+
+      if schdlr.is_dirty() {
+        if save_state {
+          schdlr.save_snapshot();
+        }
+        schdlr.set_dirty(false);
+      }
     }
   });
-  (s, r)
+  Ok((s, r))
 }
 
 #[async_trait]
@@ -124,8 +248,16 @@ pub trait Schedule: Send + Sync {
 
   fn startup(&mut self);
 
+  /// Tears down every job and executor and stops running jobs.
+  async fn shutdown(&mut self);
+
   async fn check_jobs(&mut self);
 
+  /// Earliest time, in nanoseconds, at which a job may become due.
+  fn next_wakeup(&self) -> Option<i64>;
+
+  fn now(&self) -> i64;
+
   fn is_dirty(&self) -> bool;
 
   fn set_dirty(&mut self, next: bool);
@@ -136,7 +268,7 @@ pub trait Schedule: Send + Sync {
     store: Store,
   ) -> Result<(), String>;
 
-  fn add_job(
+  async fn add_job(
     &mut self,
     alias: String,
     store_alias: String,
@@ -146,16 +278,20 @@ pub trait Schedule: Send + Sync {
     job: Box<dyn Work>,
   ) -> Result<(), String>;
 
-  fn add_executor(
+  async fn add_executor(
     &mut self,
     alias: String,
     executor: Executor,
   ) -> Result<(), String>;
 
-  fn modify_job(
+  async fn modify_job(
     &mut self,
     alias: String,
     store_alias: String,
+    executor: String,
+    start_time: i64,
+    end_time: Option<i64>,
+    job: Box<dyn Work>,
   ) -> Result<(), String>;
 
   fn pause_job(
@@ -170,23 +306,91 @@ pub trait Schedule: Send + Sync {
     store_alias: String,
   ) -> Result<(), String>;
 
-  fn remove_store(&mut self, alias: &String) -> Result<(), String>;
+  fn set_misfire(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    misfire: Option<Misfire>,
+  ) -> Result<(), String>;
+
+  fn set_timeout(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    timeout: Option<i64>,
+  ) -> Result<(), String>;
+
+  fn set_retention(
+    &mut self,
+    retention: Option<Retention>,
+  ) -> Result<(), String>;
+
+  fn cancel_run(
+    &mut self,
+    store_alias: String,
+    alias: String,
+  ) -> Result<(), String>;
+
+  async fn retry_run(
+    &mut self,
+    store_alias: String,
+    alias: String,
+    run_id: String,
+  ) -> Result<(), String>;
+
+  async fn run_job(
+    &mut self,
+    store_alias: String,
+    alias: String,
+  ) -> Result<(), String>;
+
+  fn add_trigger(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String>;
+
+  fn modify_trigger(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String>;
+
+  fn remove_trigger(
+    &mut self,
+    alias: String,
+    store_alias: String,
+    trigger_alias: String,
+  ) -> Result<(), String>;
+
+  /// Answers a read as JSON, jobs and triggers in their typetag form.
+  fn read(&self, read: Read) -> Result<Value, String>;
+
+  /// Handle on the runs in flight, usable while check_jobs is running.
+  fn cancellations(&self) -> Cancellations;
+
+  /// Handle on the scheduler's metrics, usable while check_jobs is running.
+  fn metrics(&self) -> Metrics;
+
+  async fn remove_store(&mut self, alias: &String) -> Result<(), String>;
 
-  fn remove_job(
+  async fn remove_job(
     &mut self,
     alias: String,
     job_alias: String,
   ) -> Result<(), String>;
 
-  fn remove_executor(&mut self, alias: &String) -> Result<(), String>;
+  async fn remove_executor(&mut self, alias: &String) -> Result<(), String>;
 
   fn create_snapshot(&mut self) -> Vec<u8>;
 
   fn save_snapshot(&mut self);
 
-  fn load_snapshot_from_disk(&mut self);
+  async fn load_snapshot_from_disk(&mut self);
 
-  fn load_snapshot_from_mem(&mut self, snapshot: Vec<u8>);
+  async fn load_snapshot_from_mem(&mut self, snapshot: Vec<u8>);
 
   fn vclone(&self) -> Box<dyn Schedule>;
 }
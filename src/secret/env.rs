@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::secret::Resolve;
+
+// Reads secrets from environment variables, `prefix` is prepended to names.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Secrets {
+  pub prefix: String,
+}
+
+impl Secrets {
+  pub fn new(prefix: String) -> Self {
+    Secrets { prefix }
+  }
+}
+
+#[typetag::serde(name = "EnvSecrets")]
+impl Resolve for Secrets {
+  fn resolve(&self, name: &String) -> Result<String, String> {
+    let key = format!("{}{}", &self.prefix, name);
+    env::var(&key)
+      .map_err(|_e| format!("Secret {} was not found in env var {}", name, key))
+  }
+
+  fn vclone(&self) -> Box<dyn Resolve> {
+    Box::new(self.clone())
+  }
+}
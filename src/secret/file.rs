@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::secret::Resolve;
+
+// Reads each secret from a file named after it in `dir`, the way mounted
+// secrets are laid out. Trailing newlines are dropped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Secrets {
+  pub dir: String,
+}
+
+impl Secrets {
+  pub fn new(dir: String) -> Self {
+    Secrets { dir }
+  }
+}
+
+#[typetag::serde(name = "FileSecrets")]
+impl Resolve for Secrets {
+  fn resolve(&self, name: &String) -> Result<String, String> {
+    // Names are file names, anything that could leave `dir` is refused.
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+      (Some(Component::Normal(file)), None) if file == name.as_str() => (),
+      _ => return Err(format!("Secret name {:?} isn't a file name", name)),
+    }
+    let path = Path::new(&self.dir).join(name);
+    match fs::read_to_string(&path) {
+      Ok(secret) => Ok(secret.trim_end_matches(&['\r', '\n'][..]).to_string()),
+      Err(_e) => Err(format!(
+        "Secret {} was not found in {}",
+        name,
+        path.display()
+      )),
+    }
+  }
+
+  fn vclone(&self) -> Box<dyn Resolve> {
+    Box::new(self.clone())
+  }
+}
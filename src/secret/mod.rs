@@ -0,0 +1,10 @@
+pub mod env;
+pub mod file;
+
+// Looks secrets up by name so jobs only ever store the name, never the value.
+#[typetag::serde(tag = "type")]
+pub trait Resolve: Send + Sync {
+  fn resolve(&self, name: &String) -> Result<String, String>;
+
+  fn vclone(&self) -> Box<dyn Resolve>;
+}
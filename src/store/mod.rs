@@ -1,12 +1,11 @@
 use async_trait::async_trait;
-use chrono::prelude::*;
-use colored::*;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Debug;
 
-use crate::job::{Job, Work};
+use crate::job::{Job, Misfire, Work};
+use crate::trigger::Trigger;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum JobState {
@@ -17,26 +16,10 @@ pub enum JobState {
 #[async_trait]
 pub trait Silo: Send + Sync {
   async fn startup(&mut self) -> Result<(), String> {
-    println!(
-      "{}{}",
-      "::::   Starting JobStore "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
   fn teardown(&self) -> Result<(), String> {
-    println!(
-      "{}{}",
-      "::::   Tearing Down JobStore "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
@@ -58,28 +41,10 @@ impl Store {
   }
 
   pub async fn startup(&mut self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Starting Memory JobStore "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
   pub fn teardown(&self) -> Result<(), String> {
-    println!(
-      "{}{}{}",
-      "::::   Tearing Down Memory JobStore "
-        .truecolor(0, 0, 0)
-        .bold()
-        .on_green(),
-      self.alias.truecolor(0, 0, 0).bold().on_green(),
-      "   ::::".truecolor(0, 0, 0).bold().on_green()
-    );
     Ok(())
   }
 
@@ -102,9 +67,24 @@ impl Store {
     Ok(())
   }
 
-  // TODO: Implement modify_job
-  pub fn modify_job(&mut self, _alias: &String) -> Result<(), String> {
-    Ok(())
+  pub fn modify_job(
+    &mut self,
+    alias: String,
+    executor: String,
+    start_time: i64,
+    end_time: Option<i64>,
+    job: Box<dyn Work>,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.modify_job(executor, start_time, end_time, job)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to Modify Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
   }
 
   pub fn pause_job(&mut self, alias: String) -> Result<(), String> {
@@ -133,6 +113,91 @@ impl Store {
     }
   }
 
+  pub fn set_misfire(
+    &mut self,
+    alias: String,
+    misfire: Option<Misfire>,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.set_misfire(misfire)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to set misfire for Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
+  }
+
+  pub fn set_timeout(
+    &mut self,
+    alias: String,
+    timeout: Option<i64>,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.set_timeout(timeout)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to set timeout for Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
+  }
+
+  pub fn add_trigger(
+    &mut self,
+    alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.add_trigger(trigger)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to add trigger to Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
+  }
+
+  pub fn modify_trigger(
+    &mut self,
+    alias: String,
+    trigger: Trigger,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.modify_trigger(trigger)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to modify trigger of Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
+  }
+
+  pub fn remove_trigger(
+    &mut self,
+    alias: String,
+    trigger_alias: String,
+  ) -> Result<(), String> {
+    match self.jobs.entry(alias.clone()) {
+      Entry::Occupied(mut entry) => {
+        let j = entry.get_mut();
+        j.remove_trigger(trigger_alias)
+      }
+      Entry::Vacant(_entry) => Err(format!(
+        "Failed to remove trigger from Job {}, it's not found in Store {}",
+        &alias, &self.alias
+      )),
+    }
+  }
+
   pub fn remove_job(&mut self, alias: &String) -> Result<(), String> {
     match self.jobs.remove(alias) {
       Some(_) => Ok(()),
@@ -143,11 +208,9 @@ impl Store {
     }
   }
 
-  pub fn get_due_jobs(&mut self) -> Result<Vec<&mut Job>, String> {
+  pub fn get_due_jobs(&mut self, now: i64) -> Result<Vec<&mut Job>, String> {
     let mut ready = Vec::new();
     for (_key, value) in &mut self.jobs {
-      let now = Utc::now().timestamp_nanos();
-
       if value.start_time <= now {
         ready.push(value);
       }
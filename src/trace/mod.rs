@@ -0,0 +1,81 @@
+// Spans around scheduler activity. With the `tracing` feature they're
+// `tracing` spans, otherwise they're no-ops, so call sites don't need to care
+// which.
+
+#[cfg(feature = "tracing")]
+pub use tracing::{Instrument, Span};
+
+// Evaluation of a due job: its triggers and the runs they allow.
+#[cfg(feature = "tracing")]
+pub fn job(job: &String, store: &String, executor: &String) -> Span {
+  tracing::info_span!(
+    "job",
+    job = job.as_str(),
+    store = store.as_str(),
+    executor = executor.as_str()
+  )
+}
+
+#[cfg(feature = "tracing")]
+pub fn triggers(parent: &Span) -> Span {
+  tracing::info_span!(parent: parent, "triggers")
+}
+
+// A single execution, the attempt is recorded once it's known.
+#[cfg(feature = "tracing")]
+pub fn run(parent: &Span, job: &String, scheduled: i64) -> Span {
+  tracing::info_span!(
+    parent: parent,
+    "run",
+    job = job.as_str(),
+    attempt = tracing::field::Empty,
+    scheduled
+  )
+}
+
+#[cfg(feature = "tracing")]
+pub fn record_attempt(attempt: u32) {
+  Span::current().record("attempt", &attempt);
+}
+
+#[cfg(feature = "tracing")]
+pub fn request(method: String, url: &String) -> Span {
+  tracing::info_span!("request", method = method.as_str(), url = url.as_str())
+}
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub struct Span;
+
+#[cfg(not(feature = "tracing"))]
+pub trait Instrument: Sized {
+  fn instrument(self, _span: Span) -> Self {
+    self
+  }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
+
+#[cfg(not(feature = "tracing"))]
+pub fn job(_job: &String, _store: &String, _executor: &String) -> Span {
+  Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub fn triggers(_parent: &Span) -> Span {
+  Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub fn run(_parent: &Span, _job: &String, _scheduled: i64) -> Span {
+  Span
+}
+
+#[cfg(not(feature = "tracing"))]
+pub fn record_attempt(_attempt: u32) {}
+
+#[cfg(not(feature = "tracing"))]
+pub fn request(_method: String, _url: &String) -> Span {
+  Span
+}
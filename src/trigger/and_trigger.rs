@@ -1,14 +1,13 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::ledger::Ledger;
 use crate::trigger;
+use crate::trigger::TriggerContext;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Trigger {
   alias: String,
-  left: trigger::Trigger,
-  right: trigger::Trigger,
+  triggers: Vec<trigger::Trigger>,
 }
 
 impl Trigger {
@@ -17,24 +16,42 @@ impl Trigger {
     left: trigger::Trigger,
     right: trigger::Trigger,
   ) -> Self {
-    Trigger { alias, left, right }
+    Trigger {
+      alias,
+      triggers: vec![left, right],
+    }
+  }
+
+  pub fn all(alias: String, triggers: Vec<trigger::Trigger>) -> Self {
+    Trigger { alias, triggers }
   }
 }
 
 #[async_trait]
 #[typetag::serde(name = "AndTrigger")]
 impl trigger::Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
-    self.left.trigger.should_run().await
-      && self.right.trigger.should_run().await
-  }
-
-  async fn should_run_with_ledger(&mut self, _ledger: &mut Ledger) -> bool {
-    panic!("trigger::and_trigger - DOES NOT REQUIRE SCHEDULER LEDGER")
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool {
+    for trig in &mut self.triggers {
+      if !(trig.trigger.evaluate(ctx).await) {
+        return false;
+      }
+    }
+    true
   }
 
-  async fn next(&mut self) -> Option<i64> {
-    None
+  // Every child has to agree before the job runs again, so the earliest the
+  // conjunction can fire is the latest of the children's next times.
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64> {
+    let mut next = None;
+    for trig in &mut self.triggers {
+      if let Some(v) = trig.trigger.next(ctx).await {
+        next = match next {
+          Some(curr) if curr >= v => Some(curr),
+          _ => Some(v),
+        };
+      }
+    }
+    next
   }
 
   fn vclone(&self) -> Box<dyn trigger::Fire> {
@@ -2,8 +2,8 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::job::Status;
-use crate::ledger::Ledger;
 use crate::trigger;
+use crate::trigger::TriggerContext;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Trigger {
@@ -35,28 +35,20 @@ impl Trigger {
 #[async_trait]
 #[typetag::serde(name = "JobTrigger")]
 impl trigger::Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
-    panic!("trigger::job_trigger - REQUIRES SHOULD_RUN")
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool {
+    ctx.ledger.ledger.entry(
+      &self.store,
+      &self.job,
+      &self.status,
+      &self.time,
+      &ctx.now,
+    )
   }
 
-  async fn should_run_with_ledger(&mut self, ledger: &mut Ledger) -> bool {
-    match ledger
-      .ledger
-      .entry(&self.store, &self.job, &self.status, &self.time)
-    {
-      true => true,
-      false => false,
-    }
-  }
-
-  async fn next(&mut self) -> Option<i64> {
+  async fn next(&mut self, _ctx: &TriggerContext<'_>) -> Option<i64> {
     None
   }
 
-  fn needs_ledger(&self) -> bool {
-      true
-  }
-
   fn vclone(&self) -> Box<dyn trigger::Fire> {
     Box::new(self.clone())
   }
@@ -1,5 +1,6 @@
 pub mod and_trigger;
 pub mod job_trigger;
+pub mod not_trigger;
 pub mod or_trigger;
 pub mod retry_trigger;
 pub mod test_trigger;
@@ -9,8 +10,13 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
+use crate::job::Status;
 use crate::ledger::Ledger;
 
+// How long to wait before re-evaluating a due job whose triggers did not pass,
+// and when triggers without a schedule of their own look again.
+pub const RECHECK_INTERVAL: i64 = 1000000000;
+
 #[derive(Serialize, Deserialize)]
 pub struct Trigger {
   pub alias: String,
@@ -23,18 +29,46 @@ impl Trigger {
   }
 }
 
+/// Everything a trigger may look at when deciding whether a job should run.
+/// The scheduler builds one per job evaluation.
+pub struct TriggerContext<'a> {
+  pub now: i64,
+  pub ledger: &'a Ledger,
+  pub scheduler: String,
+  pub store: String,
+  pub job: String,
+  pub last_run: Option<i64>,
+  pub last_status: Option<Status>,
+}
+
+impl<'a> TriggerContext<'a> {
+  pub fn new(
+    now: i64,
+    ledger: &'a Ledger,
+    scheduler: String,
+    store: String,
+    job: String,
+    last_run: Option<i64>,
+    last_status: Option<Status>,
+  ) -> Self {
+    TriggerContext {
+      now,
+      ledger,
+      scheduler,
+      store,
+      job,
+      last_run,
+      last_status,
+    }
+  }
+}
+
 #[async_trait]
 #[typetag::serde(tag = "type")]
 pub trait Fire: Send + Sync {
-  async fn should_run(&mut self) -> bool;
-
-  async fn should_run_with_ledger(&mut self, ledger: &mut Ledger) -> bool;
-
-  fn needs_ledger(&self) -> bool {
-    false
-  }
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool;
 
-  async fn next(&mut self) -> Option<i64>;
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64>;
 
   fn vclone(&self) -> Box<dyn Fire>;
 }
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::trigger;
+use crate::trigger::TriggerContext;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Trigger {
+  alias: String,
+  trigger: trigger::Trigger,
+}
+
+impl Trigger {
+  pub fn new(alias: String, trigger: trigger::Trigger) -> Self {
+    Trigger { alias, trigger }
+  }
+}
+
+#[async_trait]
+#[typetag::serde(name = "NotTrigger")]
+impl trigger::Fire for Trigger {
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool {
+    !(self.trigger.trigger.evaluate(ctx).await)
+  }
+
+  // The negation fires whenever the child doesn't, which has no schedule of
+  // its own, so it's looked at again after the recheck interval.
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64> {
+    Some(ctx.now + trigger::RECHECK_INTERVAL)
+  }
+
+  fn vclone(&self) -> Box<dyn trigger::Fire> {
+    Box::new(self.clone())
+  }
+}
@@ -1,14 +1,13 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::ledger::Ledger;
 use crate::trigger;
+use crate::trigger::TriggerContext;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Trigger {
   alias: String,
-  left: trigger::Trigger,
-  right: trigger::Trigger,
+  triggers: Vec<trigger::Trigger>,
 }
 
 impl Trigger {
@@ -17,24 +16,42 @@ impl Trigger {
     left: trigger::Trigger,
     right: trigger::Trigger,
   ) -> Self {
-    Trigger { alias, left, right }
+    Trigger {
+      alias,
+      triggers: vec![left, right],
+    }
+  }
+
+  pub fn any(alias: String, triggers: Vec<trigger::Trigger>) -> Self {
+    Trigger { alias, triggers }
   }
 }
 
 #[async_trait]
 #[typetag::serde(name = "OrTrigger")]
 impl trigger::Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
-    self.left.trigger.should_run().await
-      || self.right.trigger.should_run().await
-  }
-
-  async fn should_run_with_ledger(&mut self, _ledger: &mut Ledger) -> bool {
-    panic!("trigger::or_trigger - DOES NOT REQUIRE LEDGER")
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool {
+    for trig in &mut self.triggers {
+      if trig.trigger.evaluate(ctx).await {
+        return true;
+      }
+    }
+    false
   }
 
-  async fn next(&mut self) -> Option<i64> {
-    None
+  // Any single child is enough to run the job, so the disjunction fires again
+  // as soon as the earliest child does.
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64> {
+    let mut next = None;
+    for trig in &mut self.triggers {
+      if let Some(v) = trig.trigger.next(ctx).await {
+        next = match next {
+          Some(curr) if curr <= v => Some(curr),
+          _ => Some(v),
+        };
+      }
+    }
+    next
   }
 
   fn vclone(&self) -> Box<dyn trigger::Fire> {
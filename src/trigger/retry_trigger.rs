@@ -1,9 +1,8 @@
 use async_trait::async_trait;
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use crate::ledger::Ledger;
 use crate::trigger;
+use crate::trigger::TriggerContext;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Trigger {
@@ -25,7 +24,7 @@ impl Trigger {
 #[async_trait]
 #[typetag::serde(name = "RetryTrigger")]
 impl trigger::Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
+  async fn evaluate(&mut self, _ctx: &TriggerContext<'_>) -> bool {
     if (&self.attempts < &self.run) {
       self.attempts = self.attempts + 1;
       true
@@ -34,13 +33,9 @@ impl trigger::Fire for Trigger {
     }
   }
 
-  async fn should_run_with_ledger(&mut self, _ledger: &mut Ledger) -> bool {
-    panic!("trigger::retry_trigger - DOES NOT REQUIRE LEDGER")
-  }
-
-  async fn next(&mut self) -> Option<i64> {
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64> {
     if (&self.attempts < &self.run) {
-      Some(Utc::now().timestamp_nanos())
+      Some(ctx.now)
     } else {
       None
     }
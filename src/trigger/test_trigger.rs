@@ -1,8 +1,8 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::ledger::Ledger;
 use crate::trigger;
+use crate::trigger::TriggerContext;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Trigger {
@@ -24,15 +24,11 @@ impl Trigger {
 #[async_trait]
 #[typetag::serde(name = "TestTrigger")]
 impl trigger::Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
+  async fn evaluate(&mut self, _ctx: &TriggerContext<'_>) -> bool {
     self.should
   }
 
-  async fn should_run_with_ledger(&mut self, _ledger: &mut Ledger) -> bool {
-    panic!("trigger::test_trigger - DOES NOT REQUIRE LEDGER")
-  }
-
-  async fn next(&mut self) -> Option<i64> {
+  async fn next(&mut self, _ctx: &TriggerContext<'_>) -> Option<i64> {
     self.next
   }
 
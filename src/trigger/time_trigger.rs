@@ -1,10 +1,8 @@
 use async_trait::async_trait;
 use chrono::prelude::*;
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use crate::trigger::Fire;
-use crate::ledger::Ledger;
+use crate::trigger::{Fire, TriggerContext};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum Day {
@@ -95,8 +93,8 @@ enum DateTimeMatch {
 #[async_trait ]
 #[typetag::serde(name = "TimeTrigger")]
 impl Fire for Trigger {
-  async fn should_run(&mut self) -> bool {
-    let now = Utc::now();
+  async fn evaluate(&mut self, ctx: &TriggerContext<'_>) -> bool {
+    let now = Utc.timestamp_nanos(ctx.now);
     let day_match = match &self.day {
       Some(d) => match now.weekday() == day_to_chrono_day(d) {
         true => DateTimeMatch::Match,
@@ -131,17 +129,12 @@ impl Fire for Trigger {
     }
   }
 
-  async fn should_run_with_ledger(&mut self, _ledger: &mut Ledger) -> bool {
-    panic!("trigger::time_trigger - DOES NOT REQUIRE LEDGER")
-  }
-
-  async fn next(&mut self) -> Option<i64> {
-    let run: bool = self.should_run().await;
+  async fn next(&mut self, ctx: &TriggerContext<'_>) -> Option<i64> {
+    let run: bool = self.evaluate(ctx).await;
 
     if run {
-      let now = Utc::now().timestamp_nanos();
       match self.interval.clone() {
-        Some(interval) => Some(now + interval),
+        Some(interval) => Some(ctx.now + interval),
         None => None,
       }
     } else {
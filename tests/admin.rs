@@ -0,0 +1,283 @@
+use async_std::net::{SocketAddr, TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use chrono::prelude::*;
+use k9::assert_equal;
+use serde_json::{json, Value};
+
+use horoscope::admin;
+use horoscope::scheduler::{blocking, daemon, DaemonOptions};
+
+const TOKEN: &str = "secret";
+
+// Sends a request to the admin API with the given token, returning the status
+// code and the JSON body, or null when there's none.
+async fn request_as(
+  token: &str,
+  addr: SocketAddr,
+  method: &str,
+  path: &str,
+  body: Value,
+) -> (u16, Value) {
+  let body = match body {
+    Value::Null => String::new(),
+    body => body.to_string(),
+  };
+  let mut stream = TcpStream::connect(addr).await.unwrap();
+  let request = format!(
+    "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+     Authorization: Bearer {}\r\nContent-Length: {}\r\n\
+     Connection: close\r\n\r\n{}",
+    method,
+    path,
+    token,
+    body.len(),
+    body
+  );
+  stream.write_all(request.as_bytes()).await.unwrap();
+  let mut response = String::new();
+  stream.read_to_string(&mut response).await.unwrap();
+  let status = response[9..12].parse().unwrap();
+  let body = match response.find("\r\n\r\n") {
+    Some(i) if i + 4 < response.len() => {
+      serde_json::from_str(&response[i + 4..]).unwrap()
+    }
+    _ => Value::Null,
+  };
+  (status, body)
+}
+
+async fn request(
+  addr: SocketAddr,
+  method: &str,
+  path: &str,
+  body: Value,
+) -> (u16, Value) {
+  request_as(TOKEN, addr, method, path, body).await
+}
+
+// Starts a daemon without a logger and serves its admin API on a free port.
+async fn serve() -> SocketAddr {
+  let schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+  let (sender, _reader) =
+    daemon(Box::new(schdlr), DaemonOptions::default()).unwrap();
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let addr = listener.local_addr().unwrap();
+  task::spawn(admin::serve(listener, sender, format!("{}", TOKEN)));
+  addr
+}
+
+fn system_job(start_time: i64) -> Value {
+  json!({
+    "alias": "job",
+    "executor": "executor",
+    "start_time": start_time,
+    "job": {
+      "type": "SystemJob",
+      "alias": "job",
+      "script": "echo",
+      "args": ["hi"]
+    }
+  })
+}
+
+async fn setup(addr: SocketAddr) {
+  let later = Utc::now().timestamp_nanos() + 3_600_000_000_000;
+  let store = json!({ "alias": "store" });
+  let executor = json!({ "alias": "executor" });
+  let (status, _) = request(addr, "POST", "/stores", store).await;
+  assert_equal!(status, 204);
+  let (status, _) = request(addr, "POST", "/executors", executor).await;
+  assert_equal!(status, 204);
+  let (status, _) =
+    request(addr, "POST", "/stores/store/jobs", system_job(later)).await;
+  assert_equal!(status, 204);
+}
+
+#[test]
+fn admin_manages_stores_jobs_and_triggers() {
+  task::block_on(async {
+    let addr = serve().await;
+    setup(addr).await;
+
+    let (status, health) = request(addr, "GET", "/health", Value::Null).await;
+    assert_equal!(status, 200);
+    assert_equal!(&health["state"], &json!("Running"));
+    assert_equal!(&health["stores"], &json!(1));
+
+    let (_, stores) = request(addr, "GET", "/stores", Value::Null).await;
+    assert_equal!(stores, json!({ "store": ["job"] }));
+    let (_, executors) = request(addr, "GET", "/executors", Value::Null).await;
+    assert_equal!(&executors["executor"]["alias"], &json!("executor"));
+
+    let (status, job) =
+      request(addr, "GET", "/stores/store/jobs/job", Value::Null).await;
+    assert_equal!(status, 200);
+    assert_equal!(
+      &job["job"]["type"],
+      &json!("SystemJob"),
+      "Work should be in its typetag form"
+    );
+    assert_equal!(&job["job"]["args"], &json!(["hi"]));
+
+    let trigger = json!({
+      "alias": "trigger",
+      "trigger": { "type": "TestTrigger", "alias": "t", "should": true }
+    });
+    let triggers = "/stores/store/jobs/job/triggers";
+    let (status, _) = request(addr, "POST", triggers, trigger).await;
+    assert_equal!(status, 204);
+    let (_, found) = request(addr, "GET", triggers, Value::Null).await;
+    assert_equal!(&found["trigger"]["trigger"]["type"], &json!("TestTrigger"));
+    let trigger = "/stores/store/jobs/job/triggers/trigger";
+    let update = json!({
+      "alias": "ignored",
+      "trigger": { "type": "TestTrigger", "alias": "t", "should": false }
+    });
+    let (status, _) = request(addr, "PUT", trigger, update).await;
+    assert_equal!(status, 204);
+    let (_, found) = request(addr, "GET", triggers, Value::Null).await;
+    assert_equal!(
+      &found["trigger"]["trigger"]["should"],
+      &json!(false),
+      "The trigger should be replaced under the alias in the path"
+    );
+    let (status, _) = request(addr, "DELETE", trigger, Value::Null).await;
+    assert_equal!(status, 204);
+    let (_, found) = request(addr, "GET", triggers, Value::Null).await;
+    assert_equal!(found, json!({}));
+
+    let later = Utc::now().timestamp_nanos() + 7_200_000_000_000;
+    let update = json!({
+      "executor": "executor",
+      "start_time": later,
+      "job": {
+        "type": "SystemJob",
+        "alias": "job",
+        "script": "echo",
+        "args": ["bye"]
+      }
+    });
+    let (status, _) =
+      request(addr, "PUT", "/stores/store/jobs/job", update).await;
+    assert_equal!(status, 204);
+    let (_, job) =
+      request(addr, "GET", "/stores/store/jobs/job", Value::Null).await;
+    assert_equal!(&job["job"]["args"], &json!(["bye"]));
+    assert_equal!(&job["start_time"], &json!(later));
+
+    let pause = "/stores/store/jobs/job/pause";
+    let (status, _) = request(addr, "POST", pause, Value::Null).await;
+    assert_equal!(status, 204);
+    let (_, job) =
+      request(addr, "GET", "/stores/store/jobs/job", Value::Null).await;
+    assert_equal!(&job["state"], &json!("Paused"));
+
+    let timeout = "/stores/store/jobs/job/timeout";
+    let (status, _) = request(addr, "PUT", timeout, json!(5000)).await;
+    assert_equal!(status, 204);
+    let (_, jobs) =
+      request(addr, "GET", "/stores/store/jobs", Value::Null).await;
+    assert_equal!(&jobs["job"]["timeout"], &json!(5000));
+
+    let job = "/stores/store/jobs/job";
+    let (status, _) = request(addr, "DELETE", job, Value::Null).await;
+    assert_equal!(status, 204);
+    let (status, _) = request(addr, "GET", job, Value::Null).await;
+    assert_equal!(status, 404);
+    let (status, _) =
+      request(addr, "DELETE", "/stores/store", Value::Null).await;
+    assert_equal!(status, 204);
+    let (_, stores) = request(addr, "GET", "/stores", Value::Null).await;
+    assert_equal!(stores, json!({}));
+  })
+}
+
+#[test]
+fn admin_runs_jobs_and_queries_runs() {
+  task::block_on(async {
+    let addr = serve().await;
+    setup(addr).await;
+
+    let run = "/stores/store/jobs/job/run";
+    let (status, _) = request(addr, "POST", run, Value::Null).await;
+    assert_equal!(status, 204);
+
+    let (status, runs) =
+      request(addr, "GET", "/runs?store=store&job=job", Value::Null).await;
+    assert_equal!(status, 200);
+    assert_equal!(runs.as_array().unwrap().len(), 1);
+    assert_equal!(&runs[0]["status"], &json!("Success"));
+    assert_equal!(&runs[0]["executor"], &json!("executor"));
+
+    let (_, runs) =
+      request(addr, "GET", "/runs?status=Failure", Value::Null).await;
+    assert_equal!(runs, json!([]));
+    let (status, _) =
+      request(addr, "GET", "/runs?status=Nope", Value::Null).await;
+    assert_equal!(status, 400);
+  })
+}
+
+#[test]
+fn admin_reports_errors() {
+  task::block_on(async {
+    let addr = serve().await;
+
+    let (status, body) =
+      request(addr, "POST", "/stores/nope/jobs/job/pause", Value::Null).await;
+    let missing = json!({ "error": "Store nope was not found in stores" });
+    assert_equal!(status, 404, "Writes to a missing store should answer 404");
+    assert_equal!(&body, &missing);
+
+    let (status, body) =
+      request(addr, "GET", "/stores/nope/jobs", Value::Null).await;
+    assert_equal!(status, 404);
+    assert_equal!(&body, &missing);
+
+    setup(addr).await;
+    let (status, body) =
+      request(addr, "PUT", "/stores/store/jobs/nope", system_job(0)).await;
+    assert_equal!(status, 404);
+    let missing = "Failed to Modify Job nope, it's not found in Store store";
+    assert_equal!(body, json!({ "error": missing }));
+    let trigger = json!({
+      "alias": "t",
+      "trigger": { "type": "TestTrigger", "alias": "t", "should": true }
+    });
+    let path = "/stores/store/jobs/job/triggers/nope";
+    let (status, body) = request(addr, "PUT", path, trigger).await;
+    assert_equal!(status, 404);
+    assert_equal!(body, json!({ "error": "Trigger nope doesn't exists" }));
+
+    let (status, _) =
+      request(addr, "POST", "/stores", json!({ "name": "store" })).await;
+    assert_equal!(status, 400, "Bodies missing an alias should be rejected");
+
+    let (status, body) = request(addr, "GET", "/nope", Value::Null).await;
+    assert_equal!(status, 404);
+    assert_equal!(body, json!({ "error": "No route for GET /nope" }));
+  })
+}
+
+#[test]
+fn admin_requires_token() {
+  task::block_on(async {
+    let addr = serve().await;
+    let (status, body) =
+      request_as("wrong", addr, "GET", "/health", Value::Null).await;
+    assert_equal!(status, 401);
+    assert_equal!(body, json!({ "error": "Invalid admin token" }));
+
+    let schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+    let options = DaemonOptions {
+      admin: Some(format!("127.0.0.1:0")),
+      ..DaemonOptions::default()
+    };
+    let started = daemon(Box::new(schdlr), options);
+    assert_equal!(
+      started.as_ref().err(),
+      Some(&format!("Serving the admin API requires an admin token"))
+    );
+  })
+}
@@ -0,0 +1,105 @@
+use async_std::task;
+use chrono::prelude::*;
+use k9::assert_equal;
+
+use horoscope::clock::{Clock, MockClock, SystemClock};
+use horoscope::executor::Executor;
+use horoscope::job::{sys::Job, Status};
+use horoscope::scheduler::{blocking, Schedule};
+use horoscope::store::Store;
+use horoscope::trigger::{time_trigger, Trigger};
+
+#[test]
+fn system_clock_now() {
+  let before = Utc::now().timestamp_nanos();
+  let now = SystemClock::new().now();
+  let after = Utc::now().timestamp_nanos();
+
+  assert_equal!(before <= now && now <= after, true);
+}
+
+#[test]
+fn mock_clock_set_and_advance() {
+  let clock = MockClock::new(10);
+  assert_equal!(clock.now(), 10);
+
+  clock.advance(5);
+  assert_equal!(clock.now(), 15);
+
+  clock.set(100);
+  assert_equal!(clock.now(), 100);
+}
+
+#[test]
+fn mock_clock_clones_share_time() {
+  let clock = MockClock::new(10);
+  let cpy = clock.vclone();
+
+  clock.advance(90);
+  assert_equal!(cpy.now(), 100, "Cloned clock should see the advance");
+}
+
+#[test]
+fn scheduler_with_mock_clock() {
+  task::block_on(async {
+    let day: i64 = 24 * 60 * 60 * 1000000000;
+    let start = Utc.ymd(2020, 1, 1).and_hms(10, 0, 0).timestamp_nanos();
+    let clock = MockClock::new(start);
+
+    let mut schdlr = blocking::Scheduler::with_clock(
+      String::from("scheduler"),
+      None,
+      Box::new(clock.clone()),
+    );
+
+    let store = Store::new(String::from("store"));
+    let exec = Executor::new(String::from("executor"));
+    let job = Job::new(format!("job"), format!("echo"), vec![format!("test")]);
+
+    schdlr
+      .add_store(String::from("store"), store)
+      .await
+      .unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
+    schdlr
+      .add_job(
+        String::from("job"),
+        String::from("store"),
+        String::from("executor"),
+        start,
+        None,
+        Box::new(job),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .stores
+      .get_mut("store")
+      .unwrap()
+      .jobs
+      .get_mut("job")
+      .unwrap()
+      .add_trigger(Trigger::new(
+        format!("daily"),
+        Box::new(time_trigger::Trigger::new(
+          format!("daily"),
+          Some(day),
+          None,
+          Some(time_trigger::Time(10, 0)),
+        )),
+      ))
+      .unwrap();
+
+    for _ in 0..3 {
+      schdlr.check_jobs().await;
+      clock.advance(day / 2);
+      schdlr.check_jobs().await;
+      clock.advance(day / 2);
+    }
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(&job.last_run, &Some(start + 2 * day));
+    assert_equal!(&job.last_status, &Some(Status::Success));
+    assert_equal!(&job.start_time, &(start + 3 * day));
+  })
+}
@@ -2,9 +2,11 @@ use async_std::task;
 use chrono::prelude::*;
 use k9::assert_equal;
 use std::collections::HashMap;
+use std::time::Duration;
 
-use horoscope::executor::Executor;
-use horoscope::job::{sys, Job};
+use horoscope::executor::{Cancellations, Executor};
+use horoscope::job::{sys, Job, JobContext, Status, Work};
+use horoscope::ledger::{memory, Ledger};
 
 #[test]
 fn executor_init() {
@@ -90,8 +92,132 @@ fn executor_execute_failure() {
 
     assert_equal!(
       exctr.execute(&job.job).await,
-      Err(format!("Failed to successfully run echos with [\"test\"]")),
+      Err(format!(
+        "Failed to successfully run echos with [\"test\"]: {}",
+        "No such file or directory (os error 2)"
+      )),
       "job execution should fail"
     );
   })
 }
+
+#[test]
+fn executor_timeout_kills_job() {
+  task::block_on(async {
+    let marker = std::env::temp_dir().join("horoscope_executor_timeout");
+    let _ = std::fs::remove_file(&marker);
+
+    let mut sjob = sys::Job::new(
+      String::from("jobby"),
+      format!("sleep 1; touch {}", marker.display()),
+      vec![],
+    );
+    sjob.shell = true;
+    let job: Box<dyn Work> = Box::new(sjob);
+
+    let mut exctr = Executor::new(format!("exo"));
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx = JobContext::new(
+      &ledger,
+      format!("store"),
+      format!("jobby"),
+      now,
+      now,
+      1,
+    );
+    exctr.timeout = Some(100000000);
+
+    assert_equal!(
+      exctr.run(&job, &ctx, None).await.status,
+      Status::Failure(format!("timeout")),
+      "executor default timeout should apply"
+    );
+
+    task::sleep(Duration::from_millis(1500)).await;
+    assert_equal!(marker.exists(), false, "timed out job should be killed");
+
+    assert_equal!(
+      exctr.run(&job, &ctx, Some(5000000000)).await.status,
+      Status::Success,
+      "jobs finishing within their timeout shouldn't be killed"
+    );
+    assert_equal!(marker.exists(), true);
+  })
+}
+
+#[test]
+fn executor_job_timeout_overrides_default() {
+  task::block_on(async {
+    let job: Box<dyn Work> = Box::new(sys::Job::new(
+      String::from("jobby"),
+      String::from("sleep"),
+      vec![format!("0.2")],
+    ));
+
+    let mut exctr = Executor::new(format!("exo"));
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx = JobContext::new(
+      &ledger,
+      format!("store"),
+      format!("jobby"),
+      now,
+      now,
+      1,
+    );
+    exctr.timeout = Some(50000000);
+
+    assert_equal!(
+      exctr.run(&job, &ctx, Some(5000000000)).await.status,
+      Status::Success,
+      "job timeout should override the executor default"
+    );
+  })
+}
+
+#[test]
+fn executor_cancel_run() {
+  task::block_on(async {
+    let job: Box<dyn Work> = Box::new(sys::Job::new(
+      String::from("jobby"),
+      String::from("sleep"),
+      vec![format!("5")],
+    ));
+    let exctr = Executor::new(format!("exo"));
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let mut ctx = JobContext::new(
+      &ledger,
+      format!("store"),
+      format!("jobby"),
+      now,
+      now,
+      1,
+    );
+    let cancellations = Cancellations::new();
+
+    assert_equal!(
+      cancellations.cancel(&format!("store"), &format!("jobby")),
+      Err(format!(
+        "Failed to cancel Job jobby, it's not running in Store store"
+      )),
+      "cancelling a job that isn't running should fail"
+    );
+
+    ctx.cancel = cancellations.register(&format!("store"), &format!("jobby"));
+    let canceller = cancellations.clone();
+    task::spawn(async move {
+      task::sleep(Duration::from_millis(100)).await;
+      canceller.cancel(&format!("store"), &format!("jobby")).unwrap();
+    });
+
+    assert_equal!(
+      exctr.run(&job, &ctx, None).await.status,
+      Status::Failure(format!("cancelled")),
+      "run should be cancelled"
+    );
+    assert_equal!(ctx.cancel.is_cancelled(), true);
+    cancellations.finish(&format!("store"), &format!("jobby"));
+  })
+}
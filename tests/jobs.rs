@@ -1,13 +1,24 @@
 use async_std::task;
 use chrono::prelude::*;
 use k9::assert_equal;
-use mockito::mock;
+use mockito::{mock, Matcher};
 use std::collections::HashMap;
 
-use horoscope::job::network::NetType;
-use horoscope::job::{network, sys, Job, Status, Work};
+use horoscope::job::network::{Assertion, NetType};
+use horoscope::job::{network, sys, Job, JobContext, Status, Work};
 use horoscope::ledger::{memory, Ledger};
-use horoscope::trigger::{job_trigger, test_trigger, Trigger};
+use horoscope::secret::env;
+use horoscope::trigger::{job_trigger, test_trigger, Trigger, TriggerContext};
+
+// Runs func the way an executor would, outside of any scheduler.
+async fn func(job: &dyn Work) -> Status {
+  let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  let now = Utc::now().timestamp_nanos();
+  let ctx =
+    JobContext::new(&ledger, format!("store"), format!("job"), now, now, 1);
+  job.func(&ctx).await
+}
+
 #[test]
 fn sys_job_startup_ok() {
   task::block_on(async {
@@ -44,7 +55,7 @@ fn sys_job_func() {
       vec![format!("test")],
     );
     assert_equal!(
-      job.func().await,
+      func(&job).await,
       Status::Success,
       "func should run to success"
     );
@@ -60,16 +71,248 @@ fn sys_job_func_fail() {
       vec![format!("test")],
     );
     assert_equal!(
-      job.func().await,
+      func(&job).await,
       Status::Failure(format!(
-        "Failed to successfully run {} with {:?}",
+        "Failed to successfully run {} with {:?}: {}",
+        &job.script, &job.args, "No such file or directory (os error 2)"
+      )),
+      "func should fail with the reason it couldn't run"
+    );
+  });
+}
+
+#[test]
+fn sys_job_run_captures_output() {
+  task::block_on(async {
+    let job = sys::Job::new(
+      String::from("one"),
+      String::from("sh"),
+      vec![format!("-c"), format!("echo out; echo err >&2")],
+    );
+    let outcome = job.outcome().await;
+    assert_equal!(outcome.status, Status::Success, "run should succeed");
+    assert_equal!(
+      outcome.captures.get("stdout"),
+      Some(&format!("out\n")),
+      "stdout should be captured"
+    );
+    assert_equal!(
+      outcome.captures.get("stderr"),
+      Some(&format!("err\n")),
+      "stderr should be captured"
+    );
+    assert_equal!(
+      outcome.captures.get("exit_code"),
+      Some(&format!("0")),
+      "exit code should be captured"
+    );
+  });
+}
+
+#[test]
+fn sys_job_run_nonzero_exit() {
+  task::block_on(async {
+    let job = sys::Job::new(
+      String::from("one"),
+      String::from("sh"),
+      vec![format!("-c"), format!("exit 3")],
+    );
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!(
+        "Failed to successfully run {} with {:?}, exit code 3",
         &job.script, &job.args
       )),
-      "func should fail"
+      "non-zero exit should fail"
+    );
+  });
+}
+
+#[test]
+fn sys_job_run_success_codes() {
+  task::block_on(async {
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("sh"),
+      vec![format!("-c"), format!("exit 3")],
+    );
+    job.success_codes = vec![0, 3];
+    assert_equal!(
+      func(&job).await,
+      Status::Success,
+      "configured exit codes should succeed"
+    );
+  });
+}
+
+#[test]
+fn sys_job_run_output_limit() {
+  task::block_on(async {
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("echo"),
+      vec![format!("abcdefgh")],
+    );
+    job.output_limit = 4;
+    let outcome = job.outcome().await;
+    assert_equal!(
+      outcome.captures.get("stdout"),
+      Some(&format!("abcd")),
+      "stdout should be capped"
+    );
+  });
+}
+
+#[test]
+fn sys_job_run_drains_output_past_limit() {
+  task::block_on(async {
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("sh"),
+      vec![format!("-c"), format!("yes | head -c 1000000; echo done >&2")],
+    );
+    job.output_limit = 4;
+    let outcome = job.outcome().await;
+    assert_equal!(outcome.status, Status::Success);
+    assert_equal!(outcome.captures.get("stdout"), Some(&format!("y\ny\n")));
+    assert_equal!(
+      outcome.captures.get("stderr"),
+      Some(&format!("done")),
+      "output past the limit should be drained, not block the child"
+    );
+  });
+}
+
+#[test]
+fn sys_job_shell_mode() {
+  task::block_on(async {
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("echo \"$1-$2\""),
+      vec![format!("a"), format!("b")],
+    );
+    job.shell = true;
+    let outcome = job.outcome().await;
+    assert_equal!(
+      outcome.captures.get("stdout"),
+      Some(&format!("a-b\n")),
+      "args should be passed as positional parameters"
+    );
+  });
+}
+
+#[test]
+fn sys_job_env() {
+  task::block_on(async {
+    std::env::set_var("HOROSCOPE_INHERITED", "inherited");
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("echo \"$HOROSCOPE_INHERITED:$HOROSCOPE_SET\""),
+      vec![],
+    );
+    job.shell = true;
+    job.env.insert(format!("HOROSCOPE_SET"), format!("set"));
+    assert_equal!(
+      job.outcome().await.captures.get("stdout"),
+      Some(&format!("inherited:set\n")),
+      "env should be inherited and extended"
+    );
+
+    job.env_mode = sys::EnvMode::Clear;
+    assert_equal!(
+      job.outcome().await.captures.get("stdout"),
+      Some(&format!(":set\n")),
+      "env should be cleared before being set"
     );
   });
 }
 
+#[test]
+fn sys_job_cwd_and_stdin() {
+  task::block_on(async {
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("pwd"),
+      vec![],
+    );
+    job.cwd = Some(format!("/"));
+    assert_equal!(
+      job.outcome().await.captures.get("stdout"),
+      Some(&format!("/\n")),
+      "job should run in cwd"
+    );
+
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("cat"),
+      vec![],
+    );
+    job.stdin = Some(format!("payload"));
+    assert_equal!(
+      job.outcome().await.captures.get("stdout"),
+      Some(&format!("payload")),
+      "stdin should be piped to the job"
+    );
+  });
+}
+
+#[test]
+fn sys_job_stdin_with_large_output() {
+  task::block_on(async {
+    // Fills stdout well past a pipe buffer before reading any input.
+    let mut job = sys::Job::new(
+      String::from("one"),
+      String::from("head -c 200000 /dev/zero; cat > /dev/null"),
+      vec![],
+    );
+    job.shell = true;
+    job.output_limit = 16;
+    job.stdin = Some("x".repeat(200000));
+    let outcome = async_std::future::timeout(
+      std::time::Duration::from_secs(10),
+      job.outcome(),
+    )
+    .await
+    .expect("job should not deadlock on its pipes");
+    assert_equal!(outcome.status, Status::Success);
+  });
+}
+
+#[test]
+fn sys_job_config_serializes() {
+  let mut job = sys::Job::new(
+    String::from("one"),
+    String::from("cat"),
+    vec![],
+  );
+  job.env_mode = sys::EnvMode::Clear;
+  job.env.insert(format!("KEY"), format!("value"));
+  job.cwd = Some(format!("/tmp"));
+  job.stdin = Some(format!("payload"));
+  job.shell = true;
+  job.uid = Some(1000);
+  job.gid = Some(1000);
+
+  let bytes = bincode::serialize(&job).unwrap();
+  let loaded: sys::Job = bincode::deserialize(&bytes).unwrap();
+  assert_equal!(loaded.env_mode, sys::EnvMode::Clear);
+  assert_equal!(loaded.env, job.env);
+  assert_equal!(loaded.cwd, job.cwd);
+  assert_equal!(loaded.stdin, job.stdin);
+  assert_equal!(loaded.shell, true);
+  assert_equal!(loaded.uid, Some(1000));
+  assert_equal!(loaded.gid, Some(1000));
+
+  let minimal: sys::Job =
+    serde_json::from_str(r#"{ "alias": "one", "script": "cat" }"#).unwrap();
+  let defaults = sys::Job::new(format!("one"), format!("cat"), vec![]);
+  assert_equal!(
+    format!("{:?}", minimal),
+    format!("{:?}", defaults),
+    "Missing fields should take the defaults of Job::new"
+  );
+}
+
 #[test]
 fn net_job_startup_ok() {
   task::block_on(async {
@@ -77,7 +320,7 @@ fn net_job_startup_ok() {
       String::from("one"),
       String::from("https://ping.me/"),
       NetType::Get,
-      None,
+      HashMap::new(),
       None,
     );
 
@@ -99,12 +342,12 @@ fn net_job_func() {
       String::from("one"),
       format!("{}/success", url),
       NetType::Get,
-      None,
+      HashMap::new(),
       None,
     );
 
     assert_equal!(
-      job.func().await,
+      func(&job).await,
       Status::Success,
       "func should run to success"
     );
@@ -121,14 +364,414 @@ fn net_job_func_fail() {
       String::from("one"),
       format!("{}/fail", url),
       NetType::Get,
+      HashMap::new(),
       None,
+    );
+
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!("Request failed with status 500")),
+      "func should fail naming the status"
+    );
+
+    let job = network::Job::new(
+      String::from("one"),
+      format!("http://127.0.0.1:1/closed"),
+      NetType::Get,
+      HashMap::new(),
       None,
     );
+    let reason = match func(&job).await {
+      Status::Failure(reason) => reason,
+      status => format!("{:?}", status),
+    };
+    assert_equal!(
+      reason.starts_with("Unable to complete request: "),
+      true,
+      "transport failures should carry their error, got {}",
+      reason
+    );
+  });
+}
 
+#[test]
+fn net_job_methods() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let methods = vec![
+      ("PUT", NetType::Put),
+      ("PATCH", NetType::Patch),
+      ("DELETE", NetType::Delete),
+      ("HEAD", NetType::Head),
+    ];
+
+    for (name, method) in methods {
+      let m = mock(name, "/method").with_status(204).create();
+      let job = network::Job::new(
+        String::from("one"),
+        format!("{}/method", url),
+        method,
+        HashMap::new(),
+        None,
+      );
+
+      assert_equal!(
+        func(&job).await,
+        Status::Success,
+        "{} should succeed",
+        name
+      );
+      m.assert();
+    }
+  });
+}
+
+#[test]
+fn net_job_headers_and_query() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let m = mock("GET", "/headers")
+      .match_header("x-horoscope", "stars")
+      .match_query(Matcher::AllOf(vec![
+        Matcher::UrlEncoded(format!("sign"), format!("leo")),
+        Matcher::UrlEncoded(format!("day"), format!("today")),
+      ]))
+      .with_status(200)
+      .create();
+
+    let mut headers = HashMap::new();
+    headers.insert(format!("x-horoscope"), format!("stars"));
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/headers", url),
+      NetType::Get,
+      headers,
+      None,
+    );
+    job.query = vec![
+      (format!("sign"), format!("leo")),
+      (format!("day"), format!("today")),
+    ];
+
+    assert_equal!(func(&job).await, Status::Success);
+    m.assert();
+  });
+}
+
+#[test]
+fn net_job_config_defaults() {
+  let minimal: network::Job = serde_json::from_str(
+    r#"{ "alias": "one", "url": "http://localhost", "method": "Get" }"#,
+  )
+  .unwrap();
+  let defaults = network::Job::new(
+    format!("one"),
+    format!("http://localhost"),
+    NetType::Get,
+    HashMap::new(),
+    None,
+  );
+  assert_equal!(
+    format!("{:?}", minimal),
+    format!("{:?}", defaults),
+    "Missing fields should take the defaults of Job::new"
+  );
+}
+
+#[test]
+fn net_job_invalid_headers() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let mut headers = HashMap::new();
+    headers.insert(format!("x-horoscope"), format!("café"));
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/headers", url),
+      NetType::Get,
+      headers,
+      None,
+    );
     assert_equal!(
-      job.func().await,
-      Status::Failure(format!("Unable to complete request")),
-      "func should run to success"
+      func(&job).await,
+      Status::Failure(format!("Invalid value for header x-horoscope"))
+    );
+
+    job.headers = HashMap::new();
+    job.headers.insert(format!("x-café"), format!("stars"));
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!("Invalid header name \"x-café\""))
+    );
+  });
+}
+
+#[test]
+fn net_job_bodies() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let bodies = vec![
+      (
+        "/raw",
+        network::Body::Raw(format!("plain text")),
+        Matcher::Exact(format!("plain text")),
+      ),
+      (
+        "/json",
+        network::Body::Json(format!("{{\"sign\": \"leo\"}}")),
+        Matcher::Json(serde_json::json!({"sign": "leo"})),
+      ),
+      (
+        "/form",
+        network::Body::Form(vec![(format!("sign"), format!("leo lion"))]),
+        Matcher::Exact(format!("sign=leo+lion")),
+      ),
+    ];
+
+    for (path, body, matcher) in bodies {
+      let m = mock("POST", path).match_body(matcher).with_status(201).create();
+      let job = network::Job::new(
+        String::from("one"),
+        format!("{}{}", url, path),
+        NetType::Post,
+        HashMap::new(),
+        Some(body),
+      );
+
+      assert_equal!(func(&job).await, Status::Success, "{} should match", path);
+      m.assert();
+    }
+
+    let job = network::Job::new(
+      String::from("one"),
+      format!("{}/json", url),
+      NetType::Post,
+      HashMap::new(),
+      Some(network::Body::Json(format!("not json"))),
+    );
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!("Unable to parse body")),
+      "invalid json should fail"
+    );
+  });
+}
+
+#[test]
+fn net_job_success_ranges() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let _m = mock("GET", "/redirect").with_status(304).create();
+
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/redirect", url),
+      NetType::Get,
+      HashMap::new(),
+      None,
+    );
+    assert_equal!(func(&job).await, Status::Success, "3xx is a success");
+
+    job.success = vec![(200, 299)];
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!("Request failed with status 304")),
+      "statuses outside the ranges should fail"
+    );
+
+    job.success = vec![(200, 299), (304, 304)];
+    assert_equal!(func(&job).await, Status::Success);
+  });
+}
+
+#[test]
+fn net_job_run_captures_response() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let _m = mock("GET", "/capture")
+      .with_status(200)
+      .with_body("0123456789")
+      .create();
+
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/capture", url),
+      NetType::Get,
+      HashMap::new(),
+      None,
+    );
+    job.output_limit = 4;
+    let outcome = job.outcome().await;
+
+    assert_equal!(outcome.status, Status::Success);
+    assert_equal!(outcome.captures.get("status"), Some(&format!("200")));
+    assert_equal!(
+      outcome.captures.get("body"),
+      Some(&format!("0123")),
+      "body should be truncated"
+    );
+    assert_equal!(
+      outcome.captures.contains_key("latency"),
+      true,
+      "latency should be captured"
+    );
+  });
+}
+
+#[test]
+fn net_job_assertions() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let _m = mock("GET", "/assert")
+      .with_status(200)
+      .with_header("x-region", "us")
+      .with_body("{\"data\": {\"sign\": \"leo\", \"day\": 3}}")
+      .create();
+
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/assert", url),
+      NetType::Get,
+      HashMap::new(),
+      None,
+    );
+    job.assertions = vec![
+      Assertion::Status(vec![200, 201]),
+      Assertion::BodyContains(format!("leo")),
+      Assertion::JsonPointer(format!("/data/sign"), format!("leo")),
+      Assertion::JsonPointer(format!("/data/day"), format!("3")),
+      Assertion::Header(format!("X-Region")),
+      Assertion::MaxLatency(60000000000),
+    ];
+    assert_equal!(func(&job).await, Status::Success, "assertions should pass");
+
+    let failures = vec![
+      (
+        Assertion::Status(vec![201]),
+        format!("Expected status in [201], got 200"),
+      ),
+      (
+        Assertion::BodyContains(format!("virgo")),
+        format!("Expected body to contain \"virgo\""),
+      ),
+      (
+        Assertion::JsonPointer(format!("/data/day"), format!("4")),
+        format!("Expected /data/day to equal 4, got 3"),
+      ),
+      (
+        Assertion::JsonPointer(format!("/data/month"), format!("4")),
+        format!("Expected /data/month to equal 4"),
+      ),
+      (
+        Assertion::Header(format!("x-missing")),
+        format!("Expected header x-missing"),
+      ),
+    ];
+    for (assertion, reason) in failures {
+      job.assertions = vec![assertion];
+      assert_equal!(func(&job).await, Status::Failure(reason));
+    }
+
+    job.assertions = vec![Assertion::MaxLatency(0)];
+    match func(&job).await {
+      Status::Failure(reason) => assert_equal!(
+        reason.starts_with("Expected a response within 0ns"),
+        true
+      ),
+      status => panic!("Expected a latency failure, got {:?}", status),
+    }
+  });
+}
+
+#[test]
+fn net_job_auth() {
+  task::block_on(async {
+    std::env::set_var("HOROSCOPE_JOBS_TOKEN", "t0k3n");
+    std::env::set_var("HOROSCOPE_JOBS_PASSWORD", "hunter2");
+    let url = mockito::server_url();
+    let bearer = mock("GET", "/bearer")
+      .match_header("authorization", "Bearer t0k3n")
+      .with_status(200)
+      .create();
+    let basic = mock("GET", "/basic")
+      .match_header("authorization", "Basic dXNlcjpodW50ZXIy")
+      .with_status(200)
+      .create();
+
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/bearer", url),
+      NetType::Get,
+      HashMap::new(),
+      None,
+    );
+    job.auth = Some(network::Auth::Bearer(format!("TOKEN")));
+
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!(
+        "Secret TOKEN can't be resolved, Job one has no secrets provider"
+      )),
+      "secrets need a provider"
+    );
+
+    job.secrets = Some(Box::new(env::Secrets::new(format!("HOROSCOPE_JOBS_"))));
+    assert_equal!(func(&job).await, Status::Success, "bearer should be sent");
+    bearer.assert();
+
+    job.url = format!("{}/basic", url);
+    job.auth = Some(network::Auth::Basic(format!("user"), format!("PASSWORD")));
+    assert_equal!(func(&job).await, Status::Success, "basic should be sent");
+    basic.assert();
+
+    std::env::set_var("HOROSCOPE_JOBS_ACCENTED", "t0k3né");
+    job.auth = Some(network::Auth::Bearer(format!("ACCENTED")));
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!("Invalid value for header authorization")),
+      "non-ASCII secrets should fail the run"
+    );
+
+    let snapshot = bincode::serialize(&job).unwrap();
+    assert_equal!(
+      String::from_utf8_lossy(&snapshot).contains("hunter2"),
+      false,
+      "secrets shouldn't be stored in snapshots"
+    );
+  });
+}
+
+#[test]
+fn net_job_tls() {
+  task::block_on(async {
+    let url = mockito::server_url();
+    let _m = mock("GET", "/tls").with_status(200).create();
+
+    let mut job = network::Job::new(
+      String::from("one"),
+      format!("{}/tls", url),
+      NetType::Get,
+      HashMap::new(),
+      None,
+    );
+    let mut tls = network::Tls::new();
+    tls.cert = Some(format!("/etc/horoscope/client.pem"));
+    tls.key = Some(format!("/etc/horoscope/client.key"));
+    tls.key_password = Some(format!("KEY_PASSWORD"));
+    job.tls = Some(tls);
+
+    assert_equal!(
+      func(&job).await,
+      Status::Failure(format!(
+        "Secret KEY_PASSWORD can't be resolved, Job one has no secrets provider"
+      )),
+      "key password should come from the secrets provider"
+    );
+
+    job.tls = Some(network::Tls::new());
+    assert_equal!(
+      func(&job).await,
+      Status::Success,
+      "a TLS configured client should still reach plain endpoints"
     );
   });
 }
@@ -140,7 +783,7 @@ fn net_job_teardown_ok() {
       String::from("one"),
       String::from("https://ping.me/"),
       NetType::Get,
-      None,
+      HashMap::new(),
       None,
     );
 
@@ -375,8 +1018,18 @@ fn job_validate_triggers() {
       .add_trigger(Trigger::new(format!("trigga"), Box::new(jtrig)))
       .unwrap();
 
+    let ctx = TriggerContext::new(
+      Utc::now().timestamp_nanos(),
+      &ledg,
+      format!("horo"),
+      format!("store"),
+      format!("jobby"),
+      None,
+      None,
+    );
+
     assert_equal!(
-      job.validate_triggers(&mut ledg).await,
+      job.validate_triggers(&ctx).await,
       (true, Some(start_time)),
       "Job trigger should have been added"
     );
@@ -436,8 +1089,18 @@ fn job_validate_triggers_failure() {
       .add_trigger(Trigger::new(format!("trigga"), Box::new(jtrig)))
       .unwrap();
 
+    let ctx = TriggerContext::new(
+      Utc::now().timestamp_nanos(),
+      &ledg,
+      format!("horo"),
+      format!("store"),
+      format!("jobby"),
+      None,
+      None,
+    );
+
     assert_equal!(
-      job.validate_triggers(&mut ledg).await,
+      job.validate_triggers(&ctx).await,
       (false, None),
       "Job trigger should have been added"
     );
@@ -522,7 +1185,7 @@ fn trigger_vclone() {
       String::from("jobby"),
       String::from("http://ping.me"),
       NetType::Get,
-      None,
+      HashMap::new(),
       None,
     );
 
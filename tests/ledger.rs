@@ -1,9 +1,9 @@
 use chrono::prelude::*;
 use k9::assert_equal;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use horoscope::job::Status;
-use horoscope::ledger::{memory, Ledger};
+use horoscope::job::{Outcome, Status};
+use horoscope::ledger::{memory, sled, ExecutionRecord, Ledger, Query, Retention};
 
 #[test]
 pub fn ledger_creation() {
@@ -16,7 +16,8 @@ pub fn ledger_creation() {
     ml_2,
     memory::Ledger {
       data: HashMap::new(),
-      ts: vec![]
+      ts: vec![],
+      summaries: HashMap::new()
     },
     "Memory Ledger should implement PartialEQ"
   );
@@ -95,13 +96,14 @@ pub fn memory_ledger_insert() {
 
 #[test]
 pub fn memory_ledger_entry() {
+  let now = Utc::now().timestamp_nanos();
   let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
 
   ledg.ledger.insert(
     &format!("store"),
     &format!("job"),
     &Status::Waiting,
-    &Utc::now().timestamp_nanos(),
+    &now,
   );
 
   assert_equal!(
@@ -109,24 +111,33 @@ pub fn memory_ledger_entry() {
       &format!("store"),
       &format!("job"),
       &Status::Waiting,
-      &Utc::now().timestamp_nanos()
+      &now,
+      &now
     ),
     true,
     "Memory Ledger should succeed find the entry"
   );
 
   assert_equal!(
-    ledg
-      .ledger
-      .entry(&format!("store"), &format!("job"), &Status::Running, &2),
+    ledg.ledger.entry(
+      &format!("store"),
+      &format!("job"),
+      &Status::Running,
+      &2,
+      &now
+    ),
     false,
     "Memory Ledger should not find the entry"
   );
 
   assert_equal!(
-    ledg
-      .ledger
-      .entry(&format!("store"), &format!("job"), &Status::Running, &1),
+    ledg.ledger.entry(
+      &format!("store"),
+      &format!("job"),
+      &Status::Running,
+      &1,
+      &now
+    ),
     false,
     "Memory Ledger should not find the entry"
   );
@@ -136,7 +147,8 @@ pub fn memory_ledger_entry() {
       &format!("store"),
       &format!("job1"),
       &Status::Running,
-      &1
+      &1,
+      &now
     ),
     false,
     "Memory Ledger should not find the entry"
@@ -147,30 +159,482 @@ pub fn memory_ledger_entry() {
       &format!("store1"),
       &format!("job"),
       &Status::Running,
-      &1
+      &1,
+      &now
     ),
     false,
     "Memory Ledger should not find the entry"
   );
 }
 
+#[test]
+fn memory_ledger_outcomes() {
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  let mut captures = BTreeMap::new();
+  captures.insert(format!("exit_code"), format!("1"));
+  let outcome =
+    Outcome::with_captures(Status::Failure(format!("exit code 1")), captures);
+
+  ledg
+    .ledger
+    .insert_outcome(&format!("store"), &format!("job"), &outcome, &1);
+
+  assert_equal!(
+    ledg.ledger.entry(
+      &format!("store"),
+      &format!("job"),
+      &Status::Failure(format!("exit code 1")),
+      &1,
+      &1
+    ),
+    true,
+    "Outcome status should be recorded"
+  );
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job")),
+    vec![(1, outcome)],
+    "Outcome should be retrievable"
+  );
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job1")),
+    vec![],
+    "Unknown jobs should have no outcomes"
+  );
+}
+
+#[test]
+fn memory_ledger_records() {
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  let outcome = Outcome::new(Status::Failure(format!("exit code 1")));
+  let mut record = ExecutionRecord::with_outcome(
+    &format!("store"),
+    &format!("job"),
+    &outcome,
+    5,
+  );
+  record.run_id = format!("run");
+  record.executor = Some(format!("executor"));
+  record.scheduled = 2;
+  record.end = Some(9);
+  record.attempt = 3;
+  ledg.ledger.record(&record);
+
+  let recorded = ledg.ledger.last_failure(&format!("store"), &format!("job"));
+  assert_equal!(
+    recorded,
+    Some(record.clone()),
+    "Records should be kept whole"
+  );
+  assert_equal!(record.duration(), Some(4));
+  assert_equal!(record.reason(), Some(&format!("exit code 1")));
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job")),
+    vec![(5, outcome)]
+  );
+
+  let missed = ExecutionRecord::new(
+    &format!("store"),
+    &format!("job"),
+    &Status::Missed,
+    7,
+  );
+  assert_equal!((missed.scheduled, missed.end), (7, None));
+  assert_equal!(missed.duration(), None);
+  assert_equal!(missed.reason(), None);
+}
+
+fn queried_ledger() -> Ledger {
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  let runs = vec![
+    ("store", "job", Status::Success, 1),
+    ("store", "job", Status::Failure(format!("exit code 1")), 2),
+    ("store", "job1", Status::Success, 3),
+    ("store", "job", Status::Success, 4),
+    ("store1", "job", Status::Failure(format!("timeout")), 5),
+    ("store", "job", Status::Missed, 6),
+  ];
+  for (store, job, status, time) in runs {
+    ledg
+      .ledger
+      .insert(&format!("{}", store), &format!("{}", job), &status, &time);
+  }
+  ledg
+}
+
+#[test]
+fn memory_ledger_runs() {
+  let ledg = queried_ledger();
+  let times = |runs: Vec<ExecutionRecord>| -> Vec<i64> {
+    runs.into_iter().map(|record| record.start).collect()
+  };
+
+  assert_equal!(
+    times(ledg.ledger.runs(&format!("store"), &format!("job"), 2, 5)),
+    vec![2, 4],
+    "Runs should be bound by time and filtered by store and job"
+  );
+  assert_equal!(
+    times(ledg.ledger.last_runs(&format!("store"), &format!("job"), 2)),
+    vec![6, 4],
+    "Last runs should be newest first"
+  );
+  assert_equal!(
+    ledg.ledger.last_success(&format!("store"), &format!("job")),
+    Some(ExecutionRecord::new(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      4
+    ))
+  );
+  assert_equal!(
+    ledg.ledger.last_failure(&format!("store"), &format!("job")),
+    Some(ExecutionRecord::new(
+      &format!("store"),
+      &format!("job"),
+      &Status::Failure(format!("exit code 1")),
+      2
+    ))
+  );
+  assert_equal!(
+    ledg.ledger.last_failure(&format!("store"), &format!("job1")),
+    None
+  );
+}
+
+#[test]
+fn memory_ledger_query() {
+  let ledg = queried_ledger();
+  let times = |query: Query| -> Vec<i64> {
+    ledg
+      .ledger
+      .query(&query)
+      .into_iter()
+      .map(|record| record.start)
+      .collect()
+  };
+
+  assert_equal!(times(Query::new()), vec![1, 2, 3, 4, 5, 6]);
+  assert_equal!(
+    times(Query::new().store(&format!("store"))),
+    vec![1, 2, 3, 4, 6],
+    "Queries should filter by store"
+  );
+  assert_equal!(
+    times(Query::new().status(&Status::Failure(String::new()))),
+    vec![2, 5],
+    "Statuses should match on their variant"
+  );
+  assert_equal!(
+    times(Query::new().page(1, 2)),
+    vec![2, 3],
+    "Pages should skip the offset"
+  );
+  assert_equal!(
+    times(Query::new().newest_first().page(4, 10)),
+    vec![2, 1],
+    "Pages should follow the order"
+  );
+
+  let mut counts = BTreeMap::new();
+  counts.insert(format!("Failure"), 1);
+  counts.insert(format!("Missed"), 1);
+  counts.insert(format!("Success"), 2);
+  assert_equal!(
+    ledg.ledger.counts(
+      &Query::new()
+        .store(&format!("store"))
+        .job(&format!("job"))
+        .page(0, 1)
+    ),
+    counts,
+    "Counts should ignore paging"
+  );
+}
+
+#[test]
+fn memory_ledger_last_outcomes() {
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  for time in 1..4 {
+    let outcome = Outcome::with_output(Status::Success, format!("{}", time));
+    ledg
+      .ledger
+      .insert_outcome(&format!("store"), &format!("job"), &outcome, &time);
+  }
+
+  let outputs: Vec<Option<String>> = ledg
+    .ledger
+    .last_outcomes(&format!("store"), &format!("job"), 2)
+    .into_iter()
+    .map(|(_, outcome)| outcome.output)
+    .collect();
+  assert_equal!(outputs, vec![Some(format!("3")), Some(format!("2"))]);
+}
+
+#[test]
+fn memory_ledger_compaction() {
+  let times = |ledg: &Ledger, store: &str, job: &str| -> Vec<i64> {
+    let query = Query::new()
+      .store(&format!("{}", store))
+      .job(&format!("{}", job));
+    ledg
+      .ledger
+      .query(&query)
+      .into_iter()
+      .map(|record| record.start)
+      .collect()
+  };
+
+  let mut ledg = queried_ledger();
+  let mut retention = Retention::new(0);
+  retention.max_age = Some(3);
+  ledg.ledger.compact(&retention, 6);
+  assert_equal!(times(&ledg, "store", "job"), vec![4, 6], "Old runs go first");
+  assert_equal!(times(&ledg, "store1", "job"), vec![5]);
+
+  let mut ledg = queried_ledger();
+  let mut retention = Retention::new(0);
+  retention.max_per_job = Some(2);
+  ledg.ledger.compact(&retention, 6);
+  assert_equal!(times(&ledg, "store", "job"), vec![4, 6]);
+  assert_equal!(times(&ledg, "store", "job1"), vec![3]);
+  assert_equal!(
+    ledg.ledger.last_success(&format!("store"), &format!("job")),
+    Some(ExecutionRecord::new(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      4
+    )),
+    "The status index should be rebuilt"
+  );
+
+  let mut ledg = queried_ledger();
+  let mut retention = Retention::new(0);
+  retention.max_entries = Some(2);
+  ledg.ledger.compact(&retention, 6);
+  assert_equal!(ledg.ledger.query(&Query::new()).len(), 2);
+  assert_equal!(times(&ledg, "store", "job"), vec![6]);
+
+  let summary = ledg
+    .ledger
+    .summary(&format!("store"), &format!("job"))
+    .unwrap();
+  let mut counts = BTreeMap::new();
+  counts.insert(format!("Failure"), 1);
+  counts.insert(format!("Missed"), 1);
+  counts.insert(format!("Success"), 2);
+  assert_equal!(summary.counts, counts, "Summaries should survive pruning");
+  assert_equal!(summary.last_run, Some(6));
+  assert_equal!(summary.last_status, Some(Status::Missed));
+}
+
+#[test]
+fn retention_max_bytes() {
+  let entries: Vec<(String, String, i64, usize)> = vec![
+    (format!("store"), format!("job"), 1, 10),
+    (format!("store"), format!("job"), 2, 30),
+    (format!("store"), format!("job1"), 3, 20),
+    (format!("store"), format!("job"), 4, 5),
+  ];
+  let mut retention = Retention::new(0);
+  retention.max_bytes = Some(30);
+  let kept: Vec<i64> = retention
+    .prune(entries, |(s, j, t, _)| (s, j, *t), |(_, _, _, b)| *b, 4)
+    .into_iter()
+    .map(|(_, _, t, _)| t)
+    .collect();
+  assert_equal!(kept, vec![3, 4], "The oldest runs should go first");
+
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  for (time, output) in vec![(1, "x".repeat(1000)), (2, format!("y"))] {
+    let outcome = Outcome::with_output(Status::Success, output);
+    ledg
+      .ledger
+      .insert_outcome(&format!("store"), &format!("job"), &outcome, &time);
+  }
+  let mut retention = Retention::new(0);
+  retention.max_bytes = Some(500);
+  ledg.ledger.compact(&retention, 2);
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job")),
+    vec![(2, Outcome::with_output(Status::Success, format!("y")))]
+  );
+}
+
+#[test]
+fn memory_ledger_compaction_outcomes() {
+  let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+  for time in 1..4 {
+    let outcome = Outcome::with_output(Status::Success, format!("{}", time));
+    ledg
+      .ledger
+      .insert_outcome(&format!("store"), &format!("job"), &outcome, &time);
+  }
+  let mut retention = Retention::new(0);
+  retention.max_per_job = Some(1);
+  ledg.ledger.compact(&retention, 3);
+
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job")),
+    vec![(3, Outcome::with_output(Status::Success, format!("3")))]
+  );
+  assert_equal!(
+    ledg
+      .ledger
+      .summary(&format!("store"), &format!("job"))
+      .and_then(|summary| summary.last_outcome),
+    Some(Outcome::with_output(Status::Success, format!("3")))
+  );
+}
+
+fn sled_path(name: &str) -> String {
+  let path = std::env::temp_dir().join(format!("horoscope_ledger_{}", name));
+  let _ = std::fs::remove_dir_all(&path);
+  format!("{}", path.display())
+}
+
+#[test]
+fn sled_ledger_survives_restarts() {
+  let path = sled_path("restarts");
+  {
+    let mut ledg = Ledger::new(
+      format!("horo"),
+      Box::new(sled::Ledger::new(path.clone()).unwrap()),
+    );
+    ledg.ledger.insert_outcome(
+      &format!("store"),
+      &format!("job"),
+      &Outcome::with_output(Status::Success, format!("42")),
+      &1,
+    );
+  }
+
+  let ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sled::Ledger::new(path.clone()).unwrap()),
+  );
+  assert_equal!(
+    ledg.ledger.output(&format!("store"), &format!("job")),
+    Some(format!("42")),
+    "Outcomes should be read back from disk"
+  );
+  assert_equal!(
+    ledg.ledger.entry(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      &1,
+      &2
+    ),
+    true
+  );
+  assert_equal!(
+    ledg
+      .ledger
+      .summary(&format!("store"), &format!("job"))
+      .and_then(|summary| summary.last_outcome),
+    Some(Outcome::with_output(Status::Success, format!("42")))
+  );
+}
+
+#[test]
+fn sled_ledger_query() {
+  let mut ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sled::Ledger::new(sled_path("query")).unwrap()),
+  );
+  let runs = vec![
+    ("store", "job", Status::Success, -1),
+    ("store", "job", Status::Failure(format!("exit code 1")), 2),
+    ("store", "job1", Status::Success, 3),
+    ("store", "job", Status::Success, 4),
+    ("store1", "job", Status::Failure(format!("timeout")), 5),
+    ("store", "job", Status::Success, 4),
+  ];
+  for (store, job, status, time) in runs {
+    ledg
+      .ledger
+      .insert(&format!("{}", store), &format!("{}", job), &status, &time);
+  }
+  let times = |runs: Vec<ExecutionRecord>| -> Vec<i64> {
+    runs.into_iter().map(|record| record.start).collect()
+  };
+
+  assert_equal!(
+    times(ledg.ledger.query(&Query::new())),
+    vec![-1, 2, 3, 4, 4, 5]
+  );
+  assert_equal!(
+    times(ledg.ledger.runs(&format!("store"), &format!("job"), -1, 3)),
+    vec![-1, 2],
+    "Time ranges should include negative times"
+  );
+  assert_equal!(
+    times(ledg.ledger.last_runs(&format!("store"), &format!("job"), 3)),
+    vec![4, 4, 2],
+    "Runs at the same time should all be kept"
+  );
+  assert_equal!(
+    times(ledg.ledger.query(&Query::new().store(&format!("store1")))),
+    vec![5]
+  );
+
+  let mut retention = Retention::new(0);
+  retention.max_per_job = Some(1);
+  ledg.ledger.compact(&retention, 5);
+  assert_equal!(times(ledg.ledger.query(&Query::new())), vec![3, 4, 5]);
+  assert_equal!(
+    ledg
+      .ledger
+      .summary(&format!("store"), &format!("job"))
+      .map(|summary| summary.counts.values().sum::<usize>()),
+    Some(4),
+    "Summaries should survive pruning"
+  );
+
+  let mut retention = Retention::new(0);
+  retention.max_bytes = Some(0);
+  ledg.ledger.compact(&retention, 5);
+  assert_equal!(ledg.ledger.query(&Query::new()), vec![]);
+}
+
+#[test]
+fn sled_ledger_serializes_path() {
+  let path = sled_path("serializes");
+  let ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sled::Ledger::new(path.clone()).unwrap()),
+  );
+  let mut copy: Ledger =
+    bincode::deserialize(&bincode::serialize(&ledg).unwrap()).unwrap();
+  drop(ledg);
+
+  copy
+    .ledger
+    .insert(&format!("store"), &format!("job"), &Status::Success, &1);
+  assert_equal!(copy.ledger.query(&Query::new()).len(), 1);
+}
+
 #[test]
 fn ledger_vclone() {
+  let now = Utc::now().timestamp_nanos();
   let mut ledg = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
   ledg.ledger.insert(
     &format!("store"),
     &format!("job"),
     &Status::Waiting,
-    &Utc::now().timestamp_nanos(),
+    &now,
   );
-  let mut x = ledg.ledger.vclone();
+  let x = ledg.ledger.vclone();
 
   assert_equal!(
     x.entry(
       &format!("store"),
       &format!("job"),
       &Status::Waiting,
-      &Utc::now().timestamp_nanos()
+      &now,
+      &now
     ),
     true
   );
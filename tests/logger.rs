@@ -0,0 +1,209 @@
+use k9::assert_equal;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use horoscope::logger::{
+  console, facade, file, Emit, Filter, Format, Level, Logger, Record,
+};
+
+// Messages emitted by every Capture sink, as "sink:LEVEL:message".
+static CAPTURED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Capture {
+  name: String,
+}
+
+#[typetag::serde]
+impl Emit for Capture {
+  fn emit(&self, record: &Record, _format: Format) -> Result<(), String> {
+    CAPTURED.lock().unwrap().push(format!(
+      "{}:{}:{}",
+      &self.name, record.level, &record.message
+    ));
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Emit> {
+    Box::new(self.clone())
+  }
+}
+
+fn captured(name: &str) -> Vec<String> {
+  CAPTURED
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|line| line.starts_with(&format!("{}:", name)))
+    .map(|line| line[name.len() + 1..].to_string())
+    .collect()
+}
+
+fn log_all(logger: &Logger) {
+  logger.trace(format!("t"));
+  logger.debug(format!("d"));
+  logger.info(format!("i"));
+  logger.warn(format!("w"));
+  logger.err(format!("e"));
+}
+
+#[test]
+fn logger_filters_levels() {
+  let sink = |name: &str| -> Vec<Box<dyn Emit>> {
+    vec![Box::new(Capture {
+      name: format!("{}", name),
+    })]
+  };
+
+  log_all(&Logger::new(true, vec![]).with_sinks(sink("default")));
+  assert_equal!(
+    captured("default"),
+    vec![format!("INFO:i"), format!("WARN:w"), format!("ERROR:e")],
+    "Loggers should default to info"
+  );
+
+  let logger = Logger::new(true, vec![Filter::Warn])
+    .with_level(Level::Trace)
+    .with_sinks(sink("trace"));
+  log_all(&logger);
+  assert_equal!(
+    captured("trace"),
+    vec![
+      format!("TRACE:t"),
+      format!("DEBUG:d"),
+      format!("INFO:i"),
+      format!("ERROR:e")
+    ],
+    "Filtered levels should be dropped"
+  );
+
+  log_all(&Logger::new(false, vec![]).with_sinks(sink("disabled")));
+  assert_equal!(
+    captured("disabled"),
+    Vec::<String>::new(),
+    "Disabled loggers should log nothing"
+  );
+}
+
+fn log_path(name: &str) -> String {
+  let path = std::env::temp_dir().join(format!("horoscope_{}.log", name));
+  for n in 0..4 {
+    let _ = std::fs::remove_file(format!("{}.{}", path.display(), n));
+  }
+  let _ = std::fs::remove_file(&path);
+  format!("{}", path.display())
+}
+
+#[test]
+fn logger_writes_json_lines() {
+  let path = log_path("json");
+  let logger = Logger::new(true, vec![])
+    .with_format(Format::Json)
+    .with_sinks(vec![Box::new(file::Sink::new(path.clone()))]);
+  logger.info(format!("started"));
+  logger.err(format!("failed \"job\""));
+
+  let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+    .unwrap()
+    .lines()
+    .map(|line| serde_json::from_str(line).unwrap())
+    .collect();
+  assert_equal!(lines.len(), 2);
+  assert_equal!(lines[0]["level"], "INFO");
+  assert_equal!(lines[0]["message"], "started");
+  assert_equal!(lines[1]["level"], "ERROR");
+  assert_equal!(lines[1]["message"], "failed \"job\"");
+  assert_equal!(lines[1]["time"].is_string(), true);
+}
+
+#[test]
+fn file_sink_rotates() {
+  let path = log_path("rotate");
+  let logger = Logger::new(true, vec![])
+    .with_sinks(vec![Box::new(file::Sink::rotate(path.clone(), 100, 2))]);
+  for n in 0..10 {
+    logger.info(format!("message {}", n));
+  }
+
+  let read = |path: &String| std::fs::read_to_string(path).unwrap();
+  assert_equal!(read(&path).contains("message 9"), true);
+  assert_equal!(read(&format!("{}.1", path)).contains("message"), true);
+  assert_equal!(read(&format!("{}.2", path)).contains("message"), true);
+  assert_equal!(
+    std::path::Path::new(&format!("{}.3", path)).exists(),
+    false,
+    "Only `keep` rotated files should be kept"
+  );
+  assert_equal!(
+    std::fs::metadata(&path).unwrap().len() <= 100,
+    true,
+    "Files should rotate before growing past their limit"
+  );
+}
+
+struct Host;
+
+impl log::Log for Host {
+  fn enabled(&self, _metadata: &log::Metadata) -> bool {
+    true
+  }
+
+  fn log(&self, record: &log::Record) {
+    CAPTURED.lock().unwrap().push(format!(
+      "facade:{}:{}:{}",
+      record.target(),
+      record.level(),
+      record.args()
+    ));
+  }
+
+  fn flush(&self) {}
+}
+
+#[test]
+fn facade_sink_forwards_to_log() {
+  log::set_logger(&Host).unwrap();
+  log::set_max_level(log::LevelFilter::Trace);
+  let logger = Logger::new(true, vec![])
+    .with_level(Level::Debug)
+    .with_sinks(vec![Box::new(facade::Sink::new())]);
+  logger.debug(format!("checking"));
+  logger.warn(format!("missed"));
+
+  assert_equal!(
+    captured("facade"),
+    vec![
+      format!("horoscope:DEBUG:checking"),
+      format!("horoscope:WARN:missed")
+    ]
+  );
+}
+
+#[test]
+fn logger_clones_sinks() {
+  let logger = Logger::new(true, vec![])
+    .with_sinks(vec![Box::new(console::Sink::stderr())]);
+  let copy = logger.clone();
+  assert_equal!(copy.sinks.len(), 1);
+  assert_equal!(
+    serde_json::to_string(&copy).unwrap(),
+    serde_json::to_string(&logger).unwrap()
+  );
+}
+
+#[test]
+fn logger_banners_are_opt_in() {
+  let logger = Logger::new(true, vec![])
+    .with_level(Level::Debug)
+    .with_sinks(vec![Box::new(Capture {
+      name: format!("banner"),
+    })]);
+  assert_equal!(logger.banners, false, "Banners should be off by default");
+  logger.logo();
+  logger.banner(format!("Starting Executor executor"));
+  assert_equal!(
+    captured("banner"),
+    vec![format!("DEBUG:Starting Executor executor")],
+    "Banners should be plain debug records unless enabled"
+  );
+}
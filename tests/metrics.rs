@@ -0,0 +1,164 @@
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::task;
+use k9::assert_equal;
+
+use horoscope::clock::MockClock;
+use horoscope::executor::Executor;
+use horoscope::job::{sys::Job, Status};
+use horoscope::ledger::ExecutionRecord;
+use horoscope::metrics::{self, Metrics};
+use horoscope::scheduler::{blocking, Schedule};
+use horoscope::store::Store;
+
+fn record(
+  status: Status,
+  scheduled: i64,
+  start: i64,
+  end: i64,
+) -> ExecutionRecord {
+  let mut record =
+    ExecutionRecord::new(&format!("store"), &format!("job"), &status, start);
+  record.executor = Some(format!("executor"));
+  record.scheduled = scheduled;
+  record.end = Some(end);
+  record
+}
+
+#[test]
+fn metrics_render() {
+  let metrics = Metrics::new();
+  metrics.run_started(&format!("executor"));
+  assert_equal!(metrics.registry().in_flight[&format!("executor")], 1);
+  metrics.run_finished(&record(Status::Success, 0, 1_000_000, 3_000_000));
+  metrics.run_started(&format!("executor"));
+  metrics.run_finished(&record(
+    Status::Failure(format!("exit code 1")),
+    0,
+    2_000_000_000,
+    4_000_000_000,
+  ));
+  metrics.set_jobs(&format!("store"), 2);
+
+  let text = metrics.render();
+  let lines = [
+    "# TYPE horoscope_jobs_executed_total counter",
+    "horoscope_jobs_executed_total{store=\"store\",executor=\"executor\"} 2",
+    "horoscope_jobs_succeeded_total{store=\"store\",executor=\"executor\"} 1",
+    "horoscope_jobs_failed_total{store=\"store\",executor=\"executor\"} 1",
+    "# TYPE horoscope_job_duration_seconds histogram",
+    "horoscope_job_duration_seconds_bucket\
+     {store=\"store\",executor=\"executor\",le=\"0.005\"} 1",
+    "horoscope_job_duration_seconds_bucket\
+     {store=\"store\",executor=\"executor\",le=\"2.5\"} 2",
+    "horoscope_job_duration_seconds_bucket\
+     {store=\"store\",executor=\"executor\",le=\"+Inf\"} 2",
+    "horoscope_job_duration_seconds_sum\
+     {store=\"store\",executor=\"executor\"} 2.002",
+    "horoscope_scheduling_lag_seconds_count\
+     {store=\"store\",executor=\"executor\"} 2",
+    "horoscope_scheduling_lag_seconds_bucket\
+     {store=\"store\",executor=\"executor\",le=\"1\"} 1",
+    "# TYPE horoscope_jobs gauge",
+    "horoscope_jobs{store=\"store\"} 2",
+    "horoscope_executions_in_flight{executor=\"executor\"} 0",
+  ];
+  for line in lines.iter() {
+    assert!(text.lines().any(|l| l == *line), "Missing line {}", line);
+  }
+}
+
+#[test]
+fn scheduler_records_metrics() {
+  task::block_on(async {
+    let clock = MockClock::new(5000);
+    let mut schdlr = blocking::Scheduler::with_clock(
+      String::from("scheduler"),
+      None,
+      Box::new(clock.clone()),
+    );
+    let metrics = schdlr.metrics();
+
+    schdlr
+      .add_store(String::from("store"), Store::new(String::from("store")))
+      .await
+      .unwrap();
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    let job = Job::new(format!("job"), format!("echo"), vec![format!("hi")]);
+    schdlr
+      .add_job(
+        format!("job"),
+        format!("store"),
+        format!("executor"),
+        2000,
+        None,
+        Box::new(job),
+      )
+      .await
+      .unwrap();
+    assert_equal!(metrics.registry().jobs[&format!("store")], 1);
+
+    schdlr.check_jobs().await;
+    let registry = metrics.registry();
+    let key = (format!("store"), format!("executor"));
+    assert_equal!(registry.executed[&key], 1);
+    assert_equal!(registry.succeeded[&key], 1);
+    assert_equal!(registry.failed.contains_key(&key), false);
+    assert_equal!(registry.lags[&key].sum, 0.000003, "Lag should be 3000ns");
+    assert_equal!(registry.in_flight[&format!("executor")], 0);
+    assert_equal!(
+      registry.jobs[&format!("store")],
+      0,
+      "Finished jobs should leave the gauge"
+    );
+  })
+}
+
+#[test]
+fn metrics_served_over_http() {
+  task::block_on(async {
+    let metrics = Metrics::new();
+    metrics.set_jobs(&format!("store"), 3);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    task::spawn(metrics::serve(listener, metrics));
+
+    let get = |path: &'static str| async move {
+      let mut stream = TcpStream::connect(addr).await.unwrap();
+      let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+      stream.write_all(request.as_bytes()).await.unwrap();
+      let mut response = String::new();
+      stream.read_to_string(&mut response).await.unwrap();
+      response
+    };
+
+    let response = get("/metrics").await;
+    assert_equal!(response.starts_with("HTTP/1.1 200 OK"), true);
+    assert_equal!(
+      response.contains("Content-Type: text/plain; version=0.0.4"),
+      true
+    );
+    assert_equal!(
+      response.contains("horoscope_jobs{store=\"store\"} 3"),
+      true
+    );
+    assert_equal!(get("/").await.starts_with("HTTP/1.1 404"), true);
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET /metrics HTTP/1.1\r\nX: {}", "a".repeat(9000));
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert_equal!(
+      response.starts_with("HTTP/1.1 431"),
+      true,
+      "Headers past the limit should be refused"
+    );
+  })
+}
@@ -1,14 +1,26 @@
+use async_std::net::TcpListener;
 use async_std::task;
+use async_trait::async_trait;
 use chrono::prelude::*;
 use k9::assert_equal;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
 
+use horoscope::clock::{Clock, MockClock};
 use horoscope::executor::Executor;
-use horoscope::job::{sys::Job, Status};
+use horoscope::job::{
+  sys::Job, JobContext, Misfire, MisfirePolicy, Outcome, Status, Work,
+};
 // use horoscope::ledger::{memory, Ledger};
+use horoscope::ledger::{Query, Retention};
 use horoscope::logger::Logger;
-use horoscope::scheduler::{blocking, daemon, Msg, Schedule, SchedulerState};
+use horoscope::scheduler::{
+  blocking, daemon, DaemonOptions, Msg, Schedule, SchedulerState,
+};
 use horoscope::store::Store;
+use horoscope::trigger::{test_trigger, time_trigger, Trigger};
 
 #[test]
 fn scheduler_creation() {
@@ -65,9 +77,9 @@ fn scheduler_remove_store() {
     let store = Store::new(String::from("jobStore-test"));
 
     assert_equal!(schdlr.add_store(format!("store"), store).await, Ok(()));
-    assert_equal!(schdlr.remove_store(&format!("store")), Ok(()));
+    assert_equal!(schdlr.remove_store(&format!("store")).await, Ok(()));
     assert_equal!(
-      schdlr.remove_store(&format!("store")),
+      schdlr.remove_store(&format!("store")).await,
       Err(format!("Store was not found"))
     );
   })
@@ -87,9 +99,9 @@ fn scheduler_add_executor() {
 
     schdlr.add_store(format!("store"), store).await.unwrap();
 
-    assert_equal!(schdlr.add_executor(format!("exec"), exec), Ok(()));
+    assert_equal!(schdlr.add_executor(format!("exec"), exec).await, Ok(()));
     assert_equal!(
-      schdlr.add_executor(format!("exec"), exec2),
+      schdlr.add_executor(format!("exec"), exec2).await,
       Err(format!("Executor alias exec already exists"))
     );
   })
@@ -107,11 +119,11 @@ fn scheduler_remove_executor() {
     let store = Store::new(String::from("jobStore-test"));
 
     schdlr.add_store(format!("store"), store).await.unwrap();
-    schdlr.add_executor(format!("exec"), exec).unwrap();
+    schdlr.add_executor(format!("exec"), exec).await.unwrap();
 
-    assert_equal!(schdlr.remove_executor(&format!("exec")), Ok(()));
+    assert_equal!(schdlr.remove_executor(&format!("exec")).await, Ok(()));
     assert_equal!(
-      schdlr.remove_executor(&format!("exec-nope")),
+      schdlr.remove_executor(&format!("exec-nope")).await,
       Err(format!(
         "Executor exec-nope was not found in the schedulers executors"
       ))
@@ -133,7 +145,7 @@ fn scheduler_add_job() {
     let job2 = job.clone();
 
     schdlr.add_store(format!("store"), store).await.unwrap();
-    schdlr.add_executor(format!("exec"), exec).unwrap();
+    schdlr.add_executor(format!("exec"), exec).await.unwrap();
 
     schdlr
       .add_job(
@@ -144,6 +156,7 @@ fn scheduler_add_job() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     assert_equal!(
@@ -154,7 +167,7 @@ fn scheduler_add_job() {
         Utc::now().timestamp_nanos(),
         None,
         Box::new(job2),
-      ),
+      ).await,
       Err(format!("Store store-1 is not found in stores"))
     );
   })
@@ -173,7 +186,7 @@ fn scheduler_remove_job() {
     let job = Job::new(format!("job"), format!("echo"), vec![format!("lol")]);
 
     schdlr.add_store(format!("store"), store).await.unwrap();
-    schdlr.add_executor(format!("exec"), exec).unwrap();
+    schdlr.add_executor(format!("exec"), exec).await.unwrap();
     schdlr
       .add_job(
         format!("job"),
@@ -183,12 +196,16 @@ fn scheduler_remove_job() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
-    assert_equal!(schdlr.remove_job(format!("job"), format!("store"),), Ok(()));
+    assert_equal!(
+      schdlr.remove_job(format!("job"), format!("store"),).await,
+      Ok(())
+    );
 
     assert_equal!(
-      schdlr.remove_job(format!("job"), format!("store"),),
+      schdlr.remove_job(format!("job"), format!("store"),).await,
       Err(format!("Job job was not found in the Store jobStore-test"))
     );
   })
@@ -207,7 +224,7 @@ fn scheduler_pause_job() {
     let job = Job::new(format!("job"), format!("echo"), vec![format!("lol")]);
 
     schdlr.add_store(format!("store"), store).await.unwrap();
-    schdlr.add_executor(format!("exec"), exec).unwrap();
+    schdlr.add_executor(format!("exec"), exec).await.unwrap();
     schdlr
       .add_job(
         format!("job"),
@@ -217,6 +234,7 @@ fn scheduler_pause_job() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     assert_equal!(schdlr.pause_job(format!("job"), format!("store")), Ok(()));
@@ -248,7 +266,7 @@ fn scheduler_resume_job() {
     let job = Job::new(format!("job"), format!("echo"), vec![format!("lol")]);
 
     schdlr.add_store(format!("store"), store).await.unwrap();
-    schdlr.add_executor(format!("exec"), exec).unwrap();
+    schdlr.add_executor(format!("exec"), exec).await.unwrap();
     schdlr
       .add_job(
         format!("job"),
@@ -258,6 +276,7 @@ fn scheduler_resume_job() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     schdlr.pause_job(format!("job"), format!("store")).unwrap();
@@ -387,7 +406,7 @@ fn scheduler_check_jobs() {
       .add_store(String::from("store"), store)
       .await
       .unwrap();
-    schdlr.add_executor(String::from("executor"), exec).unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
     schdlr
       .add_job(
         String::from("job"),
@@ -397,6 +416,7 @@ fn scheduler_check_jobs() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     schdlr.check_jobs().await;
@@ -412,11 +432,1094 @@ fn scheduler_check_jobs() {
         None,
         Box::new(job2),
       )
+      .await
+      .unwrap();
+
+    schdlr.check_jobs().await;
+
+    assert_equal!(schdlr.stores.get("store").unwrap().jobs.len(), 1);
+  })
+}
+
+#[test]
+fn scheduler_check_jobs_records_outcome() {
+  task::block_on(async {
+    let start_time = Utc::now().timestamp_nanos() - 500000000000;
+    let next = Utc::now().timestamp_nanos() + 500000000000;
+
+    let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+
+    let store = Store::new(String::from("store"));
+    let exec = Executor::new(String::from("executor"));
+    let job = Job::new(format!("job"), format!("echo"), vec![format!("test")]);
+
+    schdlr
+      .add_store(String::from("store"), store)
+      .await
+      .unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
+    schdlr
+      .add_job(
+        String::from("job"),
+        String::from("store"),
+        String::from("executor"),
+        start_time,
+        None,
+        Box::new(job),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .stores
+      .get_mut("store")
+      .unwrap()
+      .jobs
+      .get_mut("job")
+      .unwrap()
+      .add_trigger(Trigger::new(
+        format!("trigga"),
+        Box::new(test_trigger::Trigger::new(
+          format!("trigga"),
+          true,
+          Some(next),
+        )),
+      ))
+      .unwrap();
+
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(&job.start_time, &next);
+    assert_equal!(&job.last_status, &Some(Status::Success));
+    assert_equal!(
+      schdlr.ledger.ledger.entry(
+        &format!("store"),
+        &format!("job"),
+        &Status::Success,
+        &500000000000,
+        &Utc::now().timestamp_nanos()
+      ),
+      true
+    );
+
+    let outcomes = schdlr
+      .ledger
+      .ledger
+      .outcomes(&format!("store"), &format!("job"));
+    assert_equal!(outcomes.len(), 1);
+    assert_equal!(
+      outcomes[0].1.captures.get("stdout"),
+      Some(&format!("test\n"))
+    );
+  })
+}
+
+async fn sleeping_scheduler() -> blocking::Scheduler {
+  let start_time = Utc::now().timestamp_nanos() - 500000000000;
+  let next = Utc::now().timestamp_nanos() + 500000000000;
+
+  let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+  let store = Store::new(String::from("store"));
+  let exec = Executor::new(String::from("executor"));
+  let job = Job::new(format!("job"), format!("sleep"), vec![format!("5")]);
+
+  schdlr
+    .add_store(String::from("store"), store)
+    .await
+    .unwrap();
+  schdlr.add_executor(String::from("executor"), exec).await.unwrap();
+  schdlr
+    .add_job(
+      String::from("job"),
+      String::from("store"),
+      String::from("executor"),
+      start_time,
+      None,
+      Box::new(job),
+    )
+    .await
+    .unwrap();
+  schdlr
+    .stores
+    .get_mut("store")
+    .unwrap()
+    .jobs
+    .get_mut("job")
+    .unwrap()
+    .add_trigger(Trigger::new(
+      format!("trigga"),
+      Box::new(test_trigger::Trigger::new(
+        format!("trigga"),
+        true,
+        Some(next),
+      )),
+    ))
+    .unwrap();
+
+  schdlr
+}
+
+#[test]
+fn scheduler_job_timeout() {
+  task::block_on(async {
+    let mut schdlr = sleeping_scheduler().await;
+    schdlr
+      .set_timeout(format!("job"), format!("store"), Some(100000000))
+      .unwrap();
+
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(
+      &job.last_status,
+      &Some(Status::Failure(format!("timeout"))),
+      "Job should time out"
+    );
+  })
+}
+
+#[test]
+fn scheduler_cancel_run() {
+  task::block_on(async {
+    let mut schdlr = sleeping_scheduler().await;
+    assert_equal!(
+      schdlr.cancel_run(format!("store"), format!("job")),
+      Err(format!("Failed to cancel Job job, it's not running in Store store")),
+      "Job isn't running yet"
+    );
+
+    let cancellations = schdlr.cancellations();
+    task::spawn(async move {
+      task::sleep(Duration::from_millis(100)).await;
+      cancellations
+        .cancel(&format!("store"), &format!("job"))
+        .unwrap();
+    });
+
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(
+      &job.last_status,
+      &Some(Status::Failure(format!("cancelled"))),
+      "Job should be cancelled"
+    );
+  })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Producer {
+  value: String,
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Work for Producer {
+  async fn startup(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    Status::Success
+  }
+
+  async fn run(&self, _ctx: &JobContext<'_>) -> Outcome {
+    Outcome::with_output(Status::Success, self.value.clone())
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Consumer {
+  upstream: String,
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Work for Consumer {
+  async fn startup(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  async fn func(&self, ctx: &JobContext<'_>) -> Status {
+    self.run(ctx).await.status
+  }
+
+  async fn run(&self, ctx: &JobContext<'_>) -> Outcome {
+    match ctx.output(&ctx.store, &self.upstream) {
+      Some(input) => {
+        Outcome::with_output(Status::Success, format!("{} consumed", input))
+      }
+      None => Outcome::new(Status::Failure(format!("No upstream output"))),
+    }
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+// Reports what it saw in its context, fails until its `attempt`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Inspector {
+  succeed_on: u32,
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Work for Inspector {
+  async fn startup(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  async fn func(&self, ctx: &JobContext<'_>) -> Status {
+    if ctx.attempt < self.succeed_on {
+      Status::Failure(format!("attempt {}", ctx.attempt))
+    } else {
+      Status::Success
+    }
+  }
+
+  async fn run(&self, ctx: &JobContext<'_>) -> Outcome {
+    let seen = format!(
+      "{}/{} scheduled {} started {} attempt {} cancelled {} logger {}",
+      &ctx.store,
+      &ctx.job,
+      ctx.scheduled_time,
+      ctx.start,
+      ctx.attempt,
+      ctx.cancel.is_cancelled(),
+      ctx.logger.is_some()
+    );
+    Outcome::with_output(self.func(ctx).await, seen)
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+#[test]
+fn scheduler_passes_job_context() {
+  task::block_on(async {
+    let clock = MockClock::new(5000);
+    let logger = Logger::new(false, vec![]);
+    let mut schdlr = blocking::Scheduler::with_clock(
+      String::from("scheduler"),
+      Some(logger),
+      Box::new(clock.clone()),
+    );
+
+    schdlr
+      .add_store(String::from("store"), Store::new(String::from("store")))
+      .await
+      .unwrap();
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .add_job(
+        String::from("inspector"),
+        String::from("store"),
+        String::from("executor"),
+        2000,
+        None,
+        Box::new(Inspector { succeed_on: 2 }),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .stores
+      .get_mut("store")
+      .unwrap()
+      .jobs
+      .get_mut("inspector")
+      .unwrap()
+      .add_trigger(Trigger::new(
+        format!("trigga"),
+        Box::new(test_trigger::Trigger::new(
+          format!("trigga"),
+          true,
+          Some(10000),
+        )),
+      ))
+      .unwrap();
+
+    let latest = |schdlr: &blocking::Scheduler| {
+      let outcomes = schdlr
+        .ledger
+        .ledger
+        .outcomes(&format!("store"), &format!("inspector"));
+      outcomes.last().unwrap().1.clone()
+    };
+
+    schdlr.check_jobs().await;
+    let outcome = latest(&schdlr);
+    assert_equal!(
+      outcome.output,
+      Some(format!(
+        "store/inspector scheduled 2000 started 5000 attempt 1 \
+         cancelled false logger true"
+      ))
+    );
+    assert_equal!(outcome.status, Status::Failure(format!("attempt 1")));
+
+    clock.set(6000);
+    schdlr
+      .retry_run(format!("store"), format!("inspector"), format!("run"))
+      .await
+      .unwrap();
+    let outcome = latest(&schdlr);
+    assert_equal!(
+      outcome.output,
+      Some(format!(
+        "store/inspector scheduled 6000 started 6000 attempt 2 \
+         cancelled false logger true"
+      )),
+      "Attempts should grow after a failure"
+    );
+    assert_equal!(outcome.status, Status::Success);
+
+    let runs = schdlr.ledger.ledger.last_runs(
+      &format!("store"),
+      &format!("inspector"),
+      2,
+    );
+    assert_equal!(runs[0].executor, Some(format!("executor")));
+    assert_equal!(
+      (runs[0].scheduled, runs[0].start, runs[0].end, runs[0].attempt),
+      (6000, 6000, Some(6000), 2),
+      "Runs should be recorded with their timing and attempt"
+    );
+    assert_equal!(
+      (runs[1].scheduled, runs[1].start, runs[1].attempt),
+      (2000, 5000, 1)
+    );
+    assert_equal!(runs[1].reason(), Some(&format!("attempt 1")));
+    assert_equal!(runs[1].duration(), Some(0));
+
+    schdlr
+      .retry_run(format!("store"), format!("inspector"), format!("run"))
+      .await
+      .unwrap();
+    let job = schdlr
+      .stores
+      .get("store")
+      .unwrap()
+      .jobs
+      .get("inspector")
+      .unwrap();
+    assert_equal!(job.attempt, 1, "Attempts should reset after a success");
+  })
+}
+
+// Lifecycle calls of every Hooked job, as "name:hook".
+static HOOKS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+fn hooks(name: &str) -> Vec<String> {
+  HOOKS
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|hook| hook.starts_with(&format!("{}:", name)))
+    .map(|hook| hook[name.len() + 1..].to_string())
+    .collect()
+}
+
+// Records its lifecycle calls, failing the hook named by `fail`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Hooked {
+  name: String,
+  fail: String,
+}
+
+impl Hooked {
+  fn call(&self, hook: &str) -> Result<(), String> {
+    HOOKS.lock().unwrap().push(format!("{}:{}", &self.name, hook));
+    if self.fail == hook {
+      Err(format!("{} failed", hook))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Work for Hooked {
+  async fn startup(&self) -> Result<(), String> {
+    self.call("startup")
+  }
+
+  async fn before(&self, _ctx: &JobContext<'_>) -> Result<(), String> {
+    self.call("before")
+  }
+
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    let _ = self.call("func");
+    Status::Success
+  }
+
+  async fn after(
+    &self,
+    _ctx: &JobContext<'_>,
+    _outcome: &Outcome,
+  ) -> Result<(), String> {
+    self.call("after")
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    self.call("teardown")
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+async fn hooked_scheduler(name: &str, fail: &str) -> blocking::Scheduler {
+  let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+  schdlr
+    .add_store(String::from("store"), Store::new(String::from("store")))
+    .await
+    .unwrap();
+  schdlr
+    .add_job(
+      format!("{}", name),
+      String::from("store"),
+      String::from("executor"),
+      Utc::now().timestamp_nanos() - 1000000000,
+      None,
+      Box::new(Hooked {
+        name: format!("{}", name),
+        fail: format!("{}", fail),
+      }),
+    )
+    .await
+    .unwrap();
+  schdlr
+}
+
+#[test]
+fn scheduler_job_lifecycle() {
+  task::block_on(async {
+    let mut schdlr = hooked_scheduler("lifecycle", "").await;
+    assert_equal!(
+      hooks("lifecycle"),
+      Vec::<String>::new(),
+      "Jobs shouldn't start before their executor"
+    );
+
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    assert_equal!(hooks("lifecycle"), vec!["startup"]);
+
+    schdlr.check_jobs().await;
+    assert_equal!(
+      hooks("lifecycle"),
+      vec!["startup", "before", "func", "after", "teardown"],
+      "Finished jobs should be torn down"
+    );
+
+    schdlr
+      .add_job(
+        format!("lifecycle"),
+        String::from("store"),
+        String::from("executor"),
+        Utc::now().timestamp_nanos() + 500000000000,
+        None,
+        Box::new(Hooked {
+          name: format!("lifecycle"),
+          fail: format!(""),
+        }),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .remove_job(format!("lifecycle"), format!("store"))
+      .await
+      .unwrap();
+    assert_equal!(
+      hooks("lifecycle"),
+      vec![
+        "startup", "before", "func", "after", "teardown", "startup",
+        "teardown"
+      ]
+    );
+  })
+}
+
+#[test]
+fn scheduler_paused_job() {
+  task::block_on(async {
+    let mut schdlr = hooked_scheduler("paused", "").await;
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr.pause_job(format!("paused"), format!("store")).unwrap();
+    schdlr.check_jobs().await;
+    schdlr.check_jobs().await;
+    assert_equal!(
+      hooks("paused"),
+      vec!["startup"],
+      "Paused jobs shouldn't run"
+    );
+
+    schdlr.resume_job(format!("paused"), format!("store")).unwrap();
+    assert_equal!(
+      schdlr.next_wakeup().is_some(),
+      true,
+      "Resumed jobs should be queued again"
+    );
+    schdlr.check_jobs().await;
+    assert_equal!(
+      hooks("paused"),
+      vec!["startup", "before", "func", "after", "teardown"]
+    );
+  })
+}
+
+#[test]
+fn scheduler_job_lifecycle_failures() {
+  task::block_on(async {
+    let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+    schdlr
+      .add_store(String::from("store"), Store::new(String::from("store")))
+      .await
+      .unwrap();
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    assert_equal!(
+      schdlr
+        .add_job(
+          format!("broken"),
+          String::from("store"),
+          String::from("executor"),
+          Utc::now().timestamp_nanos(),
+          None,
+          Box::new(Hooked {
+            name: format!("broken"),
+            fail: format!("startup"),
+          }),
+        )
+        .await,
+      Err(format!("Failed to start up Job broken: startup failed"))
+    );
+    assert_equal!(schdlr.stores.get("store").unwrap().jobs.len(), 0);
+    assert_equal!(
+      schdlr.ledger.ledger.entry(
+        &format!("store"),
+        &format!("broken"),
+        &Status::Failure(format!("")),
+        &1000000000,
+        &schdlr.now(),
+      ),
+      false,
+      "Jobs that fail to start aren't added, so nothing should be recorded"
+    );
+
+    for (hook, reason) in vec![
+      ("before", "Before hook of Job before failed: before failed"),
+      ("after", "After hook of Job after failed: after failed"),
+    ] {
+      let mut schdlr = hooked_scheduler(hook, hook).await;
+      schdlr
+        .add_executor(
+          String::from("executor"),
+          Executor::new(String::from("executor")),
+        )
+        .await
+        .unwrap();
+      schdlr.check_jobs().await;
+      let outcomes = schdlr
+        .ledger
+        .ledger
+        .outcomes(&format!("store"), &format!("{}", hook));
+      assert_equal!(
+        outcomes.last().map(|(_, outcome)| outcome.status.clone()),
+        Some(Status::Failure(format!("{}", reason)))
+      );
+    }
+    assert_equal!(
+      hooks("before"),
+      vec!["startup", "before", "teardown"],
+      "A failed before hook should skip the run"
+    );
+    assert_equal!(
+      hooks("after"),
+      vec!["startup", "before", "func", "after", "teardown"]
+    );
+
+    let mut schdlr = hooked_scheduler("finished", "teardown").await;
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr.check_jobs().await;
+    assert_equal!(
+      schdlr.stores.get("store").unwrap().jobs.len(),
+      0,
+      "Finished jobs should be removed even when they fail to tear down"
+    );
+    assert_equal!(
+      schdlr.ledger.ledger.entry(
+        &format!("store"),
+        &format!("finished"),
+        &Status::Failure(format!("")),
+        &1000000000,
+        &schdlr.now(),
+      ),
+      true,
+      "Teardown failures of finished jobs should be recorded"
+    );
+  })
+}
+
+#[test]
+fn scheduler_shutdown() {
+  task::block_on(async {
+    let mut schdlr = hooked_scheduler("shutdown", "").await;
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr.shutdown().await;
+    assert_equal!(&schdlr.state, &SchedulerState::Stopped);
+
+    schdlr.check_jobs().await;
+    assert_equal!(
+      hooks("shutdown"),
+      vec!["startup", "teardown"],
+      "Jobs shouldn't run after a shutdown"
+    );
+  })
+}
+
+#[test]
+fn scheduler_snapshot_replaces_jobs() {
+  task::block_on(async {
+    let mut schdlr = hooked_scheduler("replaced", "").await;
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    let snap = schdlr.create_snapshot();
+    schdlr.load_snapshot_from_mem(snap).await;
+    assert_equal!(
+      hooks("replaced"),
+      vec!["startup", "teardown"],
+      "Jobs of replaced stores should be torn down"
+    );
+    assert_equal!(
+      schdlr.stores.get("store").unwrap().jobs.contains_key("replaced"),
+      true
+    );
+  })
+}
+
+#[test]
+fn scheduler_compacts_ledger() {
+  task::block_on(async {
+    let clock = MockClock::new(1000);
+    let mut schdlr = blocking::Scheduler::with_clock(
+      String::from("scheduler"),
+      None,
+      Box::new(clock.clone()),
+    );
+    for time in 1..4 {
+      schdlr.ledger.ledger.insert(
+        &format!("store"),
+        &format!("job"),
+        &Status::Success,
+        &time,
+      );
+    }
+    let mut retention = Retention::new(500);
+    retention.max_per_job = Some(1);
+    schdlr.set_retention(Some(retention)).unwrap();
+    let runs = |schdlr: &blocking::Scheduler| {
+      schdlr.ledger.ledger.query(&Query::new()).len()
+    };
+
+    schdlr.check_jobs().await;
+    assert_equal!(runs(&schdlr), 1, "The ledger should be compacted");
+    assert_equal!(schdlr.last_compaction, 1000);
+
+    schdlr.ledger.ledger.insert(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      &4,
+    );
+    clock.advance(100);
+    schdlr.check_jobs().await;
+    assert_equal!(runs(&schdlr), 2, "Compaction should wait for the interval");
+
+    clock.advance(400);
+    schdlr.check_jobs().await;
+    assert_equal!(runs(&schdlr), 1);
+    assert_equal!(
+      schdlr
+        .ledger
+        .ledger
+        .summary(&format!("store"), &format!("job"))
+        .map(|summary| summary.counts.get("Success").cloned()),
+      Some(Some(4))
+    );
+  })
+}
+
+#[test]
+fn scheduler_passes_outputs_downstream() {
+  task::block_on(async {
+    let now = Utc::now().timestamp_nanos();
+    let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+
+    schdlr
+      .add_store(String::from("store"), Store::new(String::from("store")))
+      .await
+      .unwrap();
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .add_job(
+        String::from("consumer"),
+        String::from("store"),
+        String::from("executor"),
+        now - 1000000000,
+        None,
+        Box::new(Consumer {
+          upstream: format!("producer"),
+        }),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .add_job(
+        String::from("producer"),
+        String::from("store"),
+        String::from("executor"),
+        now - 2000000000,
+        None,
+        Box::new(Producer {
+          value: format!("42"),
+        }),
+      )
+      .await
+      .unwrap();
+
+    schdlr.check_jobs().await;
+
+    assert_equal!(
+      schdlr
+        .ledger
+        .ledger
+        .output(&format!("store"), &format!("producer")),
+      Some(format!("42")),
+      "Producer output should be stored with its ledger entry"
+    );
+    assert_equal!(
+      schdlr
+        .ledger
+        .ledger
+        .output(&format!("store"), &format!("consumer")),
+      Some(format!("42 consumed")),
+      "Consumer should read the producer's output"
+    );
+  })
+}
+
+#[test]
+fn scheduler_next_wakeup() {
+  task::block_on(async {
+    let clock = MockClock::new(1000);
+    let mut schdlr = blocking::Scheduler::with_clock(
+      String::from("scheduler"),
+      None,
+      Box::new(clock.clone()),
+    );
+
+    let store = Store::new(String::from("store"));
+    let exec = Executor::new(String::from("executor"));
+    let job = Job::new(format!("job"), format!("echo"), vec![format!("test")]);
+    let job2 = job.clone();
+
+    assert_equal!(schdlr.next_wakeup(), None);
+
+    schdlr
+      .add_store(String::from("store"), store)
+      .await
+      .unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
+    schdlr
+      .add_job(
+        String::from("late"),
+        String::from("store"),
+        String::from("executor"),
+        3000,
+        None,
+        Box::new(job),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .add_job(
+        String::from("early"),
+        String::from("store"),
+        String::from("executor"),
+        2000,
+        None,
+        Box::new(job2),
+      )
+      .await
       .unwrap();
 
+    assert_equal!(schdlr.next_wakeup(), Some(2000));
+
+    schdlr.remove_job(format!("early"), format!("store")).await.unwrap();
+    clock.set(2500);
     schdlr.check_jobs().await;
 
     assert_equal!(schdlr.stores.get("store").unwrap().jobs.len(), 1);
+    assert_equal!(schdlr.next_wakeup(), Some(3000));
+
+    clock.set(3000);
+    schdlr.check_jobs().await;
+
+    assert_equal!(schdlr.stores.get("store").unwrap().jobs.len(), 0);
+    assert_equal!(schdlr.next_wakeup(), None);
+  })
+}
+
+async fn misfired_scheduler(
+  clock: &MockClock,
+  start: i64,
+  misfire: Misfire,
+  script: Vec<String>,
+) -> blocking::Scheduler {
+  let day: i64 = 24 * 60 * 60 * 1000000000;
+  let mut schdlr = blocking::Scheduler::with_clock(
+    String::from("scheduler"),
+    None,
+    Box::new(clock.clone()),
+  );
+
+  let store = Store::new(String::from("store"));
+  let exec = Executor::new(String::from("executor"));
+  let job = Job::new(format!("job"), format!("sh"), script);
+
+  schdlr
+    .add_store(String::from("store"), store)
+    .await
+    .unwrap();
+  schdlr.add_executor(String::from("executor"), exec).await.unwrap();
+  schdlr
+    .add_job(
+      String::from("job"),
+      String::from("store"),
+      String::from("executor"),
+      start,
+      None,
+      Box::new(job),
+    )
+    .await
+    .unwrap();
+  schdlr
+    .set_misfire(format!("job"), format!("store"), Some(misfire))
+    .unwrap();
+
+  let job = schdlr
+    .stores
+    .get_mut("store")
+    .unwrap()
+    .jobs
+    .get_mut("job")
+    .unwrap();
+  job
+    .add_trigger(Trigger::new(
+      format!("daily"),
+      Box::new(time_trigger::Trigger::new(
+        format!("daily"),
+        Some(day),
+        None,
+        Some(time_trigger::Time(10, 0)),
+      )),
+    ))
+    .unwrap();
+
+  schdlr
+}
+
+#[test]
+fn scheduler_misfire_fire_all() {
+  task::block_on(async {
+    let day: i64 = 24 * 60 * 60 * 1000000000;
+    let hour: i64 = 60 * 60 * 1000000000;
+    let start = Utc.ymd(2020, 1, 1).and_hms(10, 0, 0).timestamp_nanos();
+    let clock = MockClock::new(start + 3 * day + 2 * hour);
+    let out = std::env::temp_dir().join("horoscope_misfire_fire_all");
+    let _ = std::fs::remove_file(&out);
+
+    let mut schdlr = misfired_scheduler(
+      &clock,
+      start,
+      Misfire::new(hour, MisfirePolicy::FireAll),
+      vec![format!("-c"), format!("echo run >> {}", out.display())],
+    )
+    .await;
+
+    schdlr.check_jobs().await;
+
+    let runs = std::fs::read_to_string(&out).unwrap();
+    assert_equal!(runs.lines().count(), 4, "Every missed day should run");
+    let scheduled: Vec<i64> = schdlr
+      .ledger
+      .ledger
+      .query(&Query::new().store(&format!("store")).job(&format!("job")))
+      .iter()
+      .map(|record| record.scheduled)
+      .collect();
+    assert_equal!(
+      scheduled,
+      vec![start, start + day, start + 2 * day, start + 3 * day],
+      "Each run should be scheduled at the fire time it replays"
+    );
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(&job.start_time, &(start + 4 * day));
+    assert_equal!(schdlr.next_wakeup(), Some(start + 4 * day));
+  })
+}
+
+#[test]
+fn scheduler_misfire_fire_once() {
+  task::block_on(async {
+    let day: i64 = 24 * 60 * 60 * 1000000000;
+    let hour: i64 = 60 * 60 * 1000000000;
+    let start = Utc.ymd(2020, 1, 1).and_hms(10, 0, 0).timestamp_nanos();
+    let clock = MockClock::new(start + 3 * day + 2 * hour);
+
+    let mut schdlr = misfired_scheduler(
+      &clock,
+      start,
+      Misfire::new(hour, MisfirePolicy::FireOnce),
+      vec![format!("-c"), format!("true")],
+    )
+    .await;
+
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(&job.last_run, &Some(start + 3 * day + 2 * hour));
+    assert_equal!(&job.start_time, &(start + 4 * day));
+    let run = schdlr
+      .ledger
+      .ledger
+      .last_with_status(&format!("store"), &format!("job"), &Status::Success)
+      .unwrap();
+    assert_equal!(run.scheduled, start + 3 * day);
+    assert_equal!(
+      schdlr.ledger.ledger.entry(
+        &format!("store"),
+        &format!("job"),
+        &Status::Missed,
+        &(3 * day),
+        &clock.now()
+      ),
+      true,
+      "Dropped runs should be recorded as missed"
+    );
+  })
+}
+
+#[test]
+fn scheduler_misfire_skip() {
+  task::block_on(async {
+    let day: i64 = 24 * 60 * 60 * 1000000000;
+    let hour: i64 = 60 * 60 * 1000000000;
+    let start = Utc.ymd(2020, 1, 1).and_hms(10, 0, 0).timestamp_nanos();
+    let clock = MockClock::new(start + day + 2 * hour);
+
+    let mut schdlr = misfired_scheduler(
+      &clock,
+      start,
+      Misfire::new(hour, MisfirePolicy::Skip),
+      vec![format!("-c"), format!("true")],
+    )
+    .await;
+
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(&job.last_run, &None);
+    assert_equal!(&job.start_time, &(start + 2 * day));
+    assert_equal!(
+      schdlr.ledger.ledger.entry(
+        &format!("store"),
+        &format!("job"),
+        &Status::Missed,
+        &(3 * hour),
+        &clock.now()
+      ),
+      true
+    );
+
+    clock.set(start + 2 * day + 30000000000);
+    schdlr.check_jobs().await;
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("job").unwrap();
+    assert_equal!(
+      &job.last_run,
+      &Some(start + 2 * day + 30000000000),
+      "A run inside the grace time should fire normally"
+    );
   })
 }
 
@@ -440,7 +1543,7 @@ fn scheduler_is_dirty() {
       .await
       .unwrap();
 
-    schdlr.add_executor(String::from("executor"), exec).unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
 
     schdlr
       .add_job(
@@ -451,6 +1554,7 @@ fn scheduler_is_dirty() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     assert_equal!(schdlr.is_dirty(), true);
@@ -477,7 +1581,7 @@ fn scheduler_set_dirty() {
       .await
       .unwrap();
 
-    schdlr.add_executor(String::from("executor"), exec).unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
 
     schdlr
       .add_job(
@@ -488,6 +1592,7 @@ fn scheduler_set_dirty() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     assert_equal!(schdlr.is_dirty(), true);
@@ -525,7 +1630,7 @@ fn scheduler_snapshots() {
       .await
       .unwrap();
 
-    schdlr.add_executor(String::from("executor"), exec).unwrap();
+    schdlr.add_executor(String::from("executor"), exec).await.unwrap();
 
     schdlr
       .add_job(
@@ -536,13 +1641,14 @@ fn scheduler_snapshots() {
         None,
         Box::new(job),
       )
+      .await
       .unwrap();
 
     schdlr.save_snapshot();
 
-    schdlr.remove_job(format!("job"), format!("store")).unwrap();
+    schdlr.remove_job(format!("job"), format!("store")).await.unwrap();
 
-    schdlr.load_snapshot_from_disk();
+    schdlr.load_snapshot_from_disk().await;
 
     println!("{:?}", schdlr.stores.get("store").unwrap().jobs);
 
@@ -562,11 +1668,11 @@ fn scheduler_snapshots() {
 
     let snap = schdlr.create_snapshot();
 
-    schdlr.remove_job(format!("job"), format!("store")).unwrap();
+    schdlr.remove_job(format!("job"), format!("store")).await.unwrap();
 
     assert_equal!(snap == schdlr.create_snapshot(), false);
 
-    schdlr.load_snapshot_from_mem(snap);
+    schdlr.load_snapshot_from_mem(snap).await;
 
     assert_equal!(
       &schdlr
@@ -582,6 +1688,28 @@ fn scheduler_snapshots() {
   })
 }
 
+#[test]
+fn scheduler_daemon_bind_failure() {
+  task::block_on(async {
+    let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = taken.local_addr().unwrap().to_string();
+    let schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+    let options = DaemonOptions {
+      metrics: Some(addr.clone()),
+      ..DaemonOptions::default()
+    };
+    match daemon(Box::new(schdlr), options) {
+      Ok(_) => {
+        assert_equal!(true, false, "Binding a taken address should fail")
+      }
+      Err(e) => assert_equal!(
+        e.starts_with(&format!("Unable to bind metrics listener on {}", addr)),
+        true
+      ),
+    }
+  });
+}
+
 #[test]
 fn scheduler_daemon() {
   task::block_on(async {
@@ -592,7 +1720,8 @@ fn scheduler_daemon() {
     let store = Store::new(String::from("store"));
     let exec = Executor::new(String::from("executor"));
     let job = Job::new(format!("job"), format!("echo"), vec![format!("test")]);
-    let (sender, _reader) = daemon(Box::new(schdlr), false);
+    let (sender, _reader) =
+      daemon(Box::new(schdlr), DaemonOptions::default()).unwrap();
 
     sender.send(Msg::AddStore(format!("store"), store)).await.unwrap();
     sender.send(Msg::AddExecutor(format!("executor"), exec)).await.unwrap();
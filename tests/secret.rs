@@ -0,0 +1,60 @@
+use k9::assert_equal;
+
+use horoscope::secret::{env, file, Resolve};
+
+#[test]
+fn env_secrets_resolve() {
+  std::env::set_var("HOROSCOPE_SECRET_TOKEN", "abc");
+  let secrets = env::Secrets::new(format!("HOROSCOPE_SECRET_"));
+
+  assert_equal!(
+    secrets.resolve(&format!("TOKEN")),
+    Ok(format!("abc")),
+    "Secret should be read from the prefixed env var"
+  );
+  assert_equal!(
+    secrets.resolve(&format!("MISSING")),
+    Err(format!(
+      "Secret MISSING was not found in env var HOROSCOPE_SECRET_MISSING"
+    )),
+    "Missing env vars should fail"
+  );
+}
+
+#[test]
+fn file_secrets_resolve() {
+  let dir = std::env::temp_dir().join("horoscope_file_secrets");
+  std::fs::create_dir_all(&dir).unwrap();
+  std::fs::write(dir.join("token"), "abc\n").unwrap();
+  let secrets = file::Secrets::new(format!("{}", dir.display()));
+
+  assert_equal!(
+    secrets.resolve(&format!("token")),
+    Ok(format!("abc")),
+    "Secret should be read from the file without its trailing newline"
+  );
+  assert_equal!(
+    secrets.resolve(&format!("missing")),
+    Err(format!(
+      "Secret missing was not found in {}",
+      dir.join("missing").display()
+    )),
+    "Missing files should fail"
+  );
+  for name in &["../token", "/etc/passwd", "sub/token", "..", ".", ""] {
+    assert_equal!(
+      secrets.resolve(&format!("{}", name)),
+      Err(format!("Secret name {:?} isn't a file name", name)),
+      "Names shouldn't leave the secrets directory"
+    );
+  }
+}
+
+#[test]
+fn secrets_vclone() {
+  let secrets = env::Secrets::new(format!("HOROSCOPE_VCLONE_"));
+  std::env::set_var("HOROSCOPE_VCLONE_KEY", "value");
+  let cloned = secrets.vclone();
+
+  assert_equal!(cloned.resolve(&format!("KEY")), Ok(format!("value")));
+}
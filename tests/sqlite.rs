@@ -0,0 +1,195 @@
+#![cfg(feature = "sqlite")]
+
+use k9::assert_equal;
+use std::collections::BTreeMap;
+
+use horoscope::job::{Outcome, Status};
+use horoscope::ledger::{sqlite, ExecutionRecord, Ledger, Query, Retention};
+
+fn sqlite_path(name: &str) -> String {
+  let path = std::env::temp_dir().join(format!("horoscope_{}.sqlite", name));
+  let _ = std::fs::remove_file(&path);
+  format!("{}", path.display())
+}
+
+#[test]
+fn sqlite_ledger_records_executions() {
+  let path = sqlite_path("executions");
+  let mut ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sqlite::Ledger::new(path.clone()).unwrap()),
+  );
+  ledg.ledger.insert_outcome(
+    &format!("store"),
+    &format!("job"),
+    &Outcome::with_output(Status::Success, format!("42")),
+    &1,
+  );
+  ledg.ledger.insert(
+    &format!("store"),
+    &format!("job"),
+    &Status::Failure(format!("exit code 1")),
+    &2,
+  );
+
+  let conn = rusqlite::Connection::open(&path).unwrap();
+  let rows: Vec<(String, Option<String>, i64, Option<String>)> = conn
+    .prepare(
+      "SELECT status, reason, start, output FROM executions
+       WHERE store = 'store' AND job = 'job' ORDER BY start",
+    )
+    .unwrap()
+    .query_map(rusqlite::NO_PARAMS, |row| {
+      Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect();
+  assert_equal!(
+    rows,
+    vec![
+      (format!("Success"), None, 1, Some(format!("42"))),
+      (format!("Failure"), Some(format!("exit code 1")), 2, None)
+    ],
+    "Runs should be plain rows that SQL can report on"
+  );
+
+  let mut captures = BTreeMap::new();
+  captures.insert(format!("exit_code"), format!("3"));
+  captures.insert(format!("stderr"), format!("oops"));
+  ledg.ledger.insert_outcome(
+    &format!("store"),
+    &format!("captured"),
+    &Outcome::with_captures(Status::Failure(format!("exit code 3")), captures),
+    &3,
+  );
+  let row: (String, String) = conn
+    .query_row(
+      "SELECT json_extract(outcome, '$.captures.exit_code'),
+         json_extract(outcome, '$.captures.stderr')
+       FROM executions WHERE job = 'captured'",
+      rusqlite::NO_PARAMS,
+      |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .unwrap();
+  assert_equal!(
+    row,
+    (format!("3"), format!("oops")),
+    "Captures should be queryable as JSON"
+  );
+
+  let mut record = ExecutionRecord::new(
+    &format!("store"),
+    &format!("timed"),
+    &Status::Success,
+    10,
+  );
+  record.executor = Some(format!("executor"));
+  record.scheduled = 8;
+  record.end = Some(25);
+  record.attempt = 2;
+  ledg.ledger.record(&record);
+  let row: (String, String, u32, i64, i64) = conn
+    .query_row(
+      "SELECT run_id, executor, attempt, scheduled, duration
+       FROM executions WHERE job = 'timed'",
+      rusqlite::NO_PARAMS,
+      |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+      },
+    )
+    .unwrap();
+  assert_equal!(
+    row,
+    (format!("timed-10"), format!("executor"), 2, 8, 15),
+    "Durations should be stored alongside the run's metadata"
+  );
+
+  let ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sqlite::Ledger::new(path.clone()).unwrap()),
+  );
+  assert_equal!(
+    ledg.ledger.last_failure(&format!("store"), &format!("job")),
+    Some(ExecutionRecord::new(
+      &format!("store"),
+      &format!("job"),
+      &Status::Failure(format!("exit code 1")),
+      2
+    )),
+    "Runs should survive reopening the file"
+  );
+  assert_equal!(
+    ledg.ledger.outcomes(&format!("store"), &format!("job")),
+    vec![(1, Outcome::with_output(Status::Success, format!("42")))]
+  );
+}
+
+#[test]
+fn sqlite_ledger_query() {
+  let mut ledg = Ledger::new(
+    format!("horo"),
+    Box::new(sqlite::Ledger::new(sqlite_path("query")).unwrap()),
+  );
+  let runs = vec![
+    ("store", "job", Status::Success, 1),
+    ("store", "job", Status::Failure(format!("exit code 1")), 2),
+    ("store", "job1", Status::Success, 3),
+    ("store", "job", Status::Success, 4),
+    ("store1", "job", Status::Failure(format!("timeout")), 5),
+    ("store", "job", Status::Missed, 6),
+  ];
+  for (store, job, status, time) in runs {
+    ledg
+      .ledger
+      .insert(&format!("{}", store), &format!("{}", job), &status, &time);
+  }
+  let times = |runs: Vec<ExecutionRecord>| -> Vec<i64> {
+    runs.into_iter().map(|record| record.start).collect()
+  };
+
+  assert_equal!(
+    times(ledg.ledger.runs(&format!("store"), &format!("job"), 2, 5)),
+    vec![2, 4]
+  );
+  assert_equal!(
+    times(ledg.ledger.query(&Query::new().newest_first().page(1, 2))),
+    vec![5, 4]
+  );
+  assert_equal!(
+    times(
+      ledg
+        .ledger
+        .query(&Query::new().status(&Status::Failure(String::new())))
+    ),
+    vec![2, 5]
+  );
+  assert_equal!(
+    ledg.ledger.entry(
+      &format!("store"),
+      &format!("job"),
+      &Status::Missed,
+      &1,
+      &7
+    ),
+    true
+  );
+
+  let mut retention = Retention::new(0);
+  retention.max_per_job = Some(1);
+  ledg.ledger.compact(&retention, 6);
+  assert_equal!(times(ledg.ledger.query(&Query::new())), vec![3, 5, 6]);
+  assert_equal!(
+    ledg
+      .ledger
+      .summary(&format!("store"), &format!("job"))
+      .map(|summary| summary.counts.values().sum::<usize>()),
+    Some(4),
+    "Summaries should survive pruning"
+  );
+
+  let mut retention = Retention::new(0);
+  retention.max_bytes = Some(0);
+  ledg.ledger.compact(&retention, 6);
+  assert_equal!(ledg.ledger.query(&Query::new()), vec![]);
+}
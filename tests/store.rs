@@ -190,7 +190,7 @@ fn store_get_due_jobs() {
       .unwrap();
 
     assert_equal!(
-      store.get_due_jobs().unwrap().len(),
+      store.get_due_jobs(Utc::now().timestamp_nanos()).unwrap().len(),
       1,
       "Store should have a job that is due"
     )
@@ -223,7 +223,7 @@ fn store_get_due_jobs_2() {
       .unwrap();
 
     assert_equal!(
-      store.get_due_jobs().unwrap().len(),
+      store.get_due_jobs(Utc::now().timestamp_nanos()).unwrap().len(),
       0,
       "Store should not have a job that is due"
     )
@@ -275,7 +275,7 @@ fn store_get_due_jobs_3() {
       .unwrap();
 
     assert_equal!(
-      store.get_due_jobs().unwrap().len(),
+      store.get_due_jobs(Utc::now().timestamp_nanos()).unwrap().len(),
       1,
       "Store should have a job that is due"
     )
@@ -0,0 +1,210 @@
+#![cfg(feature = "tracing")]
+
+use async_std::task;
+use k9::assert_equal;
+use mockito::mock;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+use horoscope::clock::MockClock;
+use horoscope::executor::Executor;
+use horoscope::job::network::{self, NetType};
+use horoscope::job::{sys, Work};
+use horoscope::scheduler::{blocking, Schedule};
+use horoscope::store::Store;
+
+// (metadata, "field=value" pairs, parent index) of every span, by id - 1.
+type Spans =
+  Arc<Mutex<Vec<(&'static Metadata<'static>, Vec<String>, Option<usize>)>>>;
+
+thread_local! {
+  // Spans entered on this thread, the innermost last.
+  static CURRENT: RefCell<Vec<usize>> = RefCell::new(vec![]);
+}
+
+struct Fields<'a>(&'a mut Vec<String>);
+
+impl<'a> Visit for Fields<'a> {
+  fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+    self.0.push(format!("{}={:?}", field.name(), value));
+  }
+
+  fn record_str(&mut self, field: &Field, value: &str) {
+    self.0.push(format!("{}={}", field.name(), value));
+  }
+}
+
+#[derive(Clone, Default)]
+struct Collector {
+  spans: Spans,
+}
+
+impl Subscriber for Collector {
+  fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+    true
+  }
+
+  fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+    let mut fields = vec![];
+    attrs.record(&mut Fields(&mut fields));
+    let parent = match attrs.parent() {
+      Some(id) => Some(id.into_u64() as usize - 1),
+      None if attrs.is_contextual() => {
+        CURRENT.with(|current| current.borrow().last().cloned())
+      }
+      None => None,
+    };
+    let mut spans = self.spans.lock().unwrap();
+    spans.push((attrs.metadata(), fields, parent));
+    Id::from_u64(spans.len() as u64)
+  }
+
+  fn record(&self, span: &Id, values: &Record<'_>) {
+    let mut spans = self.spans.lock().unwrap();
+    let fields = &mut spans[span.into_u64() as usize - 1].1;
+    values.record(&mut Fields(fields));
+  }
+
+  fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+  fn event(&self, _event: &Event<'_>) {}
+
+  fn enter(&self, span: &Id) {
+    let index = span.into_u64() as usize - 1;
+    CURRENT.with(|current| current.borrow_mut().push(index));
+  }
+
+  fn exit(&self, _span: &Id) {
+    CURRENT.with(|current| current.borrow_mut().pop());
+  }
+
+  fn current_span(&self) -> Current {
+    match CURRENT.with(|current| current.borrow().last().cloned()) {
+      Some(index) => {
+        let metadata = self.spans.lock().unwrap()[index].0;
+        Current::new(Id::from_u64(index as u64 + 1), metadata)
+      }
+      None => Current::none(),
+    }
+  }
+}
+
+// Spans named `name`, as (fields, parent's name).
+fn spans(collector: &Collector, name: &str) -> Vec<(Vec<String>, String)> {
+  let spans = collector.spans.lock().unwrap();
+  spans
+    .iter()
+    .filter(|(metadata, _, _)| metadata.name() == name)
+    .map(|(_, fields, parent)| {
+      let parent =
+        parent.map(|p| spans[p].0.name().to_string()).unwrap_or_default();
+      (fields.clone(), parent)
+    })
+    .collect()
+}
+
+#[test]
+fn scheduler_traces_job_runs() {
+  let collector = Collector::default();
+  tracing::subscriber::with_default(collector.clone(), || {
+    task::block_on(async {
+      let clock = MockClock::new(5000);
+      let mut schdlr = blocking::Scheduler::with_clock(
+        String::from("scheduler"),
+        None,
+        Box::new(clock.clone()),
+      );
+      schdlr
+        .add_store(String::from("store"), Store::new(String::from("store")))
+        .await
+        .unwrap();
+      schdlr
+        .add_executor(
+          String::from("executor"),
+          Executor::new(String::from("executor")),
+        )
+        .await
+        .unwrap();
+      let job = sys::Job::new(format!("job"), format!("echo"), vec![]);
+      schdlr
+        .add_job(
+          format!("job"),
+          format!("store"),
+          format!("executor"),
+          2000,
+          None,
+          Box::new(job),
+        )
+        .await
+        .unwrap();
+      schdlr.check_jobs().await;
+    })
+  });
+
+  assert_equal!(
+    spans(&collector, "job"),
+    vec![(
+      vec![
+        format!("job=job"),
+        format!("store=store"),
+        format!("executor=executor")
+      ],
+      String::new()
+    )]
+  );
+  assert_equal!(
+    spans(&collector, "triggers"),
+    vec![(vec![], format!("job"))],
+    "Trigger evaluation should be a child of the job"
+  );
+  assert_equal!(
+    spans(&collector, "run"),
+    vec![(
+      vec![
+        format!("job=job"),
+        format!("scheduled=2000"),
+        format!("attempt=1")
+      ],
+      format!("job")
+    )],
+    "Runs should record their attempt"
+  );
+}
+
+#[test]
+fn network_requests_are_traced() {
+  let url = mockito::server_url();
+  let _m = mock("GET", "/traced").with_status(200).create();
+  let job: Box<dyn Work> = Box::new(network::Job::new(
+    String::from("net"),
+    format!("{}/traced", url),
+    NetType::Get,
+    HashMap::new(),
+    None,
+  ));
+
+  let collector = Collector::default();
+  tracing::subscriber::with_default(collector.clone(), || {
+    task::block_on(async {
+      Executor::new(String::from("executor"))
+        .execute(&job)
+        .await
+        .unwrap();
+    })
+  });
+
+  assert_equal!(
+    spans(&collector, "request"),
+    vec![(
+      vec![format!("method=GET"), format!("url={}/traced", url)],
+      format!("run")
+    )],
+    "Requests should be children of the run"
+  );
+}
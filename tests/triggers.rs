@@ -5,10 +5,22 @@ use k9::assert_equal;
 use horoscope::job::Status;
 use horoscope::ledger::{memory, Ledger};
 use horoscope::trigger::{
-  and_trigger, job_trigger, or_trigger, retry_trigger, test_trigger,
-  time_trigger, Fire, Trigger,
+  and_trigger, job_trigger, not_trigger, or_trigger, retry_trigger,
+  test_trigger, time_trigger, Fire, Trigger, TriggerContext, RECHECK_INTERVAL,
 };
 
+fn context(ledger: &Ledger) -> TriggerContext {
+  TriggerContext::new(
+    Utc::now().timestamp_nanos(),
+    ledger,
+    format!("horo"),
+    format!("store"),
+    format!("job"),
+    None,
+    None,
+  )
+}
+
 #[test]
 fn trigger_creation() {
   task::block_on(async {
@@ -21,8 +33,11 @@ fn trigger_creation() {
       "trigger name should be set"
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      trig.trigger.should_run().await,
+      trig.trigger.evaluate(&ctx).await,
       false,
       "Trigger should be set"
     );
@@ -93,8 +108,11 @@ fn time_trigger_should_run_with_day() {
       None,
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       true,
       "Time Trigger should run because of the day"
     )
@@ -112,8 +130,11 @@ fn time_trigger_should_run_with_time() {
       Some(time_trigger::Time(now.hour(), now.minute())),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       true,
       "Time Trigger should run because of the time"
     )
@@ -131,8 +152,11 @@ fn time_trigger_should_run_with_day_time() {
       Some(time_trigger::Time(now.hour(), now.minute())),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       true,
       "Time Trigger should run because of the day and time match"
     )
@@ -150,8 +174,11 @@ fn time_trigger_should_run_fail_on_day_mismatch() {
       Some(time_trigger::Time(now.hour(), now.minute())),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       false,
       "Time Trigger should fail because of the day mismatch"
     )
@@ -169,8 +196,11 @@ fn time_trigger_should_run_fail_on_time_mismatch() {
       Some(time_trigger::Time(now.hour() + 1, now.minute() + 1)),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       false,
       "Time Trigger should fail because of the time mismatch"
     )
@@ -188,8 +218,11 @@ fn time_trigger_should_run_fail_on_day_time_mismatch() {
       Some(time_trigger::Time(now.hour() + 1, now.minute() + 1)),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       false,
       "Time Trigger should fail because of the day & time mismatch"
     )
@@ -206,8 +239,11 @@ fn time_trigger_should_run_fail_on_day_mismatch_nothing() {
       None,
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run().await,
+      tt.evaluate(&ctx).await,
       false,
       "Time Trigger should fail because of the day mismatch and nothing time"
     )
@@ -225,34 +261,13 @@ fn time_trigger_should_run_fail_on_nothing_time_mismatch() {
       Some(time_trigger::Time(now.hour() + 1, now.minute() + 1)),
     );
 
-    assert_equal!(
-      tt.should_run().await,
-      false,
-      "Time Trigger should fail because of the nothing and time mismatch"
-    )
-  });
-}
-
-#[should_panic(expected = "trigger::time_trigger - DOES NOT REQUIRE LEDGER")]
-#[test]
-fn time_trigger_should_panic_with_ledger() {
-  task::block_on(async {
-    let now = Utc::now();
-
-    let mut tt = time_trigger::Trigger::new(
-      format!("trigga"),
-      None,
-      None,
-      Some(time_trigger::Time(now.hour() + 1, now.minute() + 1)),
-    );
-
-    let mut ledg =
+    let ledg =
       Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
-
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.should_run_with_ledger(&mut ledg).await,
+      tt.evaluate(&ctx).await,
       false,
-      "Time Trigger should fail because it doesn't require ledger"
+      "Time Trigger should fail because of the nothing and time mismatch"
     )
   });
 }
@@ -269,8 +284,11 @@ fn time_trigger_next_some() {
       Some(time_trigger::Time(now.hour(), now.minute())),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.next().await != None,
+      tt.next(&ctx).await != None,
       true,
       "Time Trigger next should result in Some(interval) with interval"
     )
@@ -289,8 +307,11 @@ fn time_trigger_next_none() {
       Some(time_trigger::Time(now.hour(), now.minute())),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.next().await,
+      tt.next(&ctx).await,
       None,
       "Time Trigger next should result in None without interval"
     )
@@ -309,8 +330,11 @@ fn time_trigger_next_mismatch() {
       Some(time_trigger::Time(now.hour() + 1, now.minute() + 1)),
     );
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      tt.next().await,
+      tt.next(&ctx).await,
       None,
       "Time Trigger next should result in None without interval"
     )
@@ -329,7 +353,10 @@ fn time_vclone() {
     );
 
     let mut tt2 = tt.vclone();
-    assert_equal!(tt2.next().await, None);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(tt2.next(&ctx).await, None);
   })
 }
 
@@ -354,8 +381,9 @@ fn job_trigger_should_run_with_ledger() {
       &time,
     );
 
+    let ctx = context(&ledg);
     assert_equal!(
-      jt.should_run_with_ledger(&mut ledg).await,
+      jt.evaluate(&ctx).await,
       true,
       "Job Trigger should run"
     );
@@ -376,36 +404,15 @@ fn job_trigger_should_run_with_ledger_false() {
       time,
     );
 
+    let ctx = context(&ledg);
     assert_equal!(
-      jt.should_run_with_ledger(&mut ledg).await,
+      jt.evaluate(&ctx).await,
       false,
       "Job Trigger shouldn't run"
     );
   });
 }
 
-#[should_panic(expected = "trigger::job_trigger - REQUIRES SHOULD_RUN")]
-#[test]
-fn job_trigger_should_run() {
-  task::block_on(async {
-    let time = Utc::now().timestamp_nanos();
-
-    let mut jt = job_trigger::Trigger::new(
-      format!("trigga"),
-      format!("job"),
-      format!("store"),
-      Status::Waiting,
-      time,
-    );
-
-    assert_equal!(
-      jt.should_run().await,
-      true,
-      "Job Trigger should_run should panic"
-    );
-  });
-}
-
 #[test]
 fn job_trigger_next() {
   task::block_on(async {
@@ -427,24 +434,8 @@ fn job_trigger_next() {
       &time,
     );
 
-    assert_equal!(jt.next().await, None, "Job Trigger next");
-  });
-}
-
-#[test]
-fn job_trigger_needs_ledger() {
-  task::block_on(async {
-    let time = Utc::now().timestamp_nanos();
-
-    let jt = job_trigger::Trigger::new(
-      format!("trigga"),
-      format!("job"),
-      format!("store"),
-      Status::Waiting,
-      time,
-    );
-
-    assert_equal!(jt.needs_ledger(), true, "Job Trigger should need ledger");
+    let ctx = context(&ledg);
+    assert_equal!(jt.next(&ctx).await, None, "Job Trigger next");
   });
 }
 
@@ -462,7 +453,10 @@ fn job_vclone() {
     );
 
     let mut jt2 = jt.vclone();
-    assert_equal!(jt2.next().await, None);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(jt2.next(&ctx).await, None);
   });
 }
 
@@ -471,55 +465,45 @@ fn retry_trigger_should_run() {
   task::block_on(async {
     let mut rt = retry_trigger::Trigger::new(format!("triggy"), 3);
 
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     assert_equal!(
-      rt.should_run().await,
+      rt.evaluate(&ctx).await,
       true,
       "Retry Trigger should run on 1st attempt"
     );
     assert_equal!(
-      rt.should_run().await,
+      rt.evaluate(&ctx).await,
       true,
       "Retry Trigger should run on 2nd attempt"
     );
 
     assert_equal!(
-      rt.should_run().await,
+      rt.evaluate(&ctx).await,
       true,
       "Retry Trigger should run on 3rd attempt"
     );
 
     assert_equal!(
-      rt.should_run().await,
+      rt.evaluate(&ctx).await,
       false,
       "Retry Trigger should not run on 4th attempt"
     );
   });
 }
 
-#[should_panic(expected = "trigger::retry_trigger - DOES NOT REQUIRE LEDGER")]
-#[test]
-fn retry_trigger_should_run_with_ledger() {
-  task::block_on(async {
-    let mut ledg =
-      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
-    let mut rt = retry_trigger::Trigger::new(format!("triggy"), 3);
-
-    assert_equal!(
-      rt.should_run_with_ledger(&mut ledg).await,
-      false,
-      "Retry Trigger should panic as it doesn't need the ledger"
-    );
-  });
-}
-
 #[test]
 fn retry_trigger_next() {
   task::block_on(async {
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     let mut rt = retry_trigger::Trigger::new(format!("triggy"), 3);
 
-    rt.should_run().await;
+    rt.evaluate(&ctx).await;
     assert_equal!(
-      rt.next().await != None,
+      rt.next(&ctx).await != None,
       true,
       "Retry Trigger should succeed with next"
     )
@@ -529,18 +513,25 @@ fn retry_trigger_next() {
 #[test]
 fn retry_trigger_next_fail() {
   task::block_on(async {
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
     let mut rt = retry_trigger::Trigger::new(format!("triggy"), 2);
 
-    rt.should_run().await;
+    rt.evaluate(&ctx).await;
     assert_equal!(
-      rt.next().await != None,
+      rt.next(&ctx).await != None,
       true,
       "Retry Trigger should succeed with next"
     );
 
-    rt.should_run().await;
+    rt.evaluate(&ctx).await;
 
-    assert_equal!(rt.next().await, None, "Retry Trigger should fail with next");
+    assert_equal!(
+      rt.next(&ctx).await,
+      None,
+      "Retry Trigger should fail with next"
+    );
   });
 }
 
@@ -550,7 +541,10 @@ fn retry_vclone() {
     let rt = retry_trigger::Trigger::new(format!("triggy"), 0);
 
     let mut rt2 = rt.vclone();
-    assert_equal!(rt2.next().await, None);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(rt2.next(&ctx).await, None);
   });
 }
 
@@ -568,7 +562,10 @@ fn and_trigger_should_run() {
 
     let mut at = and_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(at.should_run().await, true, "And Trigger should run");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(at.evaluate(&ctx).await, true, "And Trigger should run");
   });
 }
 
@@ -586,22 +583,36 @@ fn and_trigger_should_not_run() {
 
     let mut at = and_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(at.should_run().await, false, "And Trigger shouldn't run");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(at.evaluate(&ctx).await, false, "And Trigger shouldn't run");
   });
 }
 
-#[should_panic(
-  expected = "trigger::and_trigger - DOES NOT REQUIRE SCHEDULER LEDGER"
-)]
 #[test]
 fn and_trigger_should_run_with_ledger() {
   task::block_on(async {
+    let time = Utc::now().timestamp_nanos();
     let mut ledg =
       Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
 
+    ledg.ledger.insert(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      &time,
+    );
+
     let left = Trigger::new(
       format!("left"),
-      Box::new(test_trigger::Trigger::new(format!("left"), true, None)),
+      Box::new(job_trigger::Trigger::new(
+        format!("left"),
+        format!("job"),
+        format!("store"),
+        Status::Success,
+        time,
+      )),
     );
     let right = Trigger::new(
       format!("right"),
@@ -609,11 +620,75 @@ fn and_trigger_should_run_with_ledger() {
     );
 
     let mut at = and_trigger::Trigger::new(format!("triggy"), left, right);
+    let ctx = context(&ledg);
+    assert_equal!(
+      at.evaluate(&ctx).await,
+      true,
+      "And Trigger should pass the ledger to the Job Trigger"
+    );
+  })
+}
 
+#[test]
+fn and_trigger_should_not_run_with_ledger() {
+  task::block_on(async {
+    let time = Utc::now().timestamp_nanos();
+    let mut ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+
+    let left = Trigger::new(
+      format!("left"),
+      Box::new(job_trigger::Trigger::new(
+        format!("left"),
+        format!("job"),
+        format!("store"),
+        Status::Success,
+        time,
+      )),
+    );
+    let right = Trigger::new(
+      format!("right"),
+      Box::new(test_trigger::Trigger::new(format!("right"), true, None)),
+    );
+
+    let mut at = and_trigger::Trigger::new(format!("triggy"), left, right);
+
+    let ctx = context(&ledg);
     assert_equal!(
-      at.should_run_with_ledger(&mut ledg).await,
+      at.evaluate(&ctx).await,
       false,
-      "And Trigger can't run with ledger"
+      "And Trigger shouldn't run without a ledger entry"
+    );
+  })
+}
+
+#[test]
+fn and_trigger_all() {
+  task::block_on(async {
+    let triggers = vec![
+      Trigger::new(
+        format!("one"),
+        Box::new(test_trigger::Trigger::new(format!("one"), true, Some(1))),
+      ),
+      Trigger::new(
+        format!("two"),
+        Box::new(test_trigger::Trigger::new(format!("two"), true, Some(3))),
+      ),
+      Trigger::new(
+        format!("three"),
+        Box::new(test_trigger::Trigger::new(format!("three"), true, Some(2))),
+      ),
+    ];
+
+    let mut at = and_trigger::Trigger::all(format!("triggy"), triggers);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(at.evaluate(&ctx).await, true, "And Trigger should run");
+    assert_equal!(
+      at.next(&ctx).await,
+      Some(3),
+      "And Trigger next should be the latest of its children"
     );
   })
 }
@@ -632,7 +707,14 @@ fn and_trigger_next() {
 
     let mut at = and_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(at.next().await, None, "And Trigger should always give None");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(
+      at.next(&ctx).await,
+      None,
+      "And Trigger should give None when no child has a next time"
+    );
   })
 }
 
@@ -651,7 +733,10 @@ fn and_vclone() {
     let at = and_trigger::Trigger::new(format!("triggy"), left, right);
     let mut at2 = at.vclone();
 
-    assert_equal!(at2.next().await, None);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(at2.next(&ctx).await, None);
   });
 }
 
@@ -669,7 +754,10 @@ fn or_trigger_should_run() {
 
     let mut ot = or_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(ot.should_run().await, true, "Or Trigger should run");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(ot.evaluate(&ctx).await, true, "Or Trigger should run");
   });
 }
 
@@ -687,7 +775,10 @@ fn or_trigger_should_run_with_half() {
 
     let mut ot = or_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(ot.should_run().await, true, "Or Trigger should run");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(ot.evaluate(&ctx).await, true, "Or Trigger should run");
   });
 }
 
@@ -705,32 +796,80 @@ fn or_trigger_should_not_run() {
 
     let mut ot = or_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(ot.should_run().await, false, "Or Trigger should not run");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(ot.evaluate(&ctx).await, false, "Or Trigger should not run");
   });
 }
 
-#[should_panic(expected = "trigger::or_trigger - DOES NOT REQUIRE LEDGER")]
 #[test]
 fn or_trigger_should_run_with_ledger() {
   task::block_on(async {
+    let time = Utc::now().timestamp_nanos();
     let mut ledg =
       Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
 
+    ledg.ledger.insert(
+      &format!("store"),
+      &format!("job"),
+      &Status::Success,
+      &time,
+    );
+
     let left = Trigger::new(
       format!("left"),
-      Box::new(test_trigger::Trigger::new(format!("left"), true, None)),
+      Box::new(test_trigger::Trigger::new(format!("left"), false, None)),
     );
     let right = Trigger::new(
       format!("right"),
-      Box::new(test_trigger::Trigger::new(format!("right"), true, None)),
+      Box::new(job_trigger::Trigger::new(
+        format!("right"),
+        format!("job"),
+        format!("store"),
+        Status::Success,
+        time,
+      )),
     );
 
     let mut ot = or_trigger::Trigger::new(format!("triggy"), left, right);
+    let ctx = context(&ledg);
+    assert_equal!(
+      ot.evaluate(&ctx).await,
+      true,
+      "Or Trigger should pass the ledger to the Job Trigger"
+    );
+  })
+}
+
+#[test]
+fn or_trigger_any() {
+  task::block_on(async {
+    let triggers = vec![
+      Trigger::new(
+        format!("one"),
+        Box::new(test_trigger::Trigger::new(format!("one"), false, Some(2))),
+      ),
+      Trigger::new(
+        format!("two"),
+        Box::new(test_trigger::Trigger::new(format!("two"), false, None)),
+      ),
+      Trigger::new(
+        format!("three"),
+        Box::new(test_trigger::Trigger::new(format!("three"), true, Some(5))),
+      ),
+    ];
 
+    let mut ot = or_trigger::Trigger::any(format!("triggy"), triggers);
+
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(ot.evaluate(&ctx).await, true, "Or Trigger should run");
     assert_equal!(
-      ot.should_run_with_ledger(&mut ledg).await,
-      false,
-      "Or Trigger can't run with ledger"
+      ot.next(&ctx).await,
+      Some(2),
+      "Or Trigger next should be the earliest of its children"
     );
   })
 }
@@ -749,7 +888,14 @@ fn or_trigger_next() {
 
     let mut ot = or_trigger::Trigger::new(format!("triggy"), left, right);
 
-    assert_equal!(ot.next().await, None, "Or Trigger should always give None");
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(
+      ot.next(&ctx).await,
+      None,
+      "Or Trigger should give None when no child has a next time"
+    );
   })
 }
 
@@ -768,6 +914,87 @@ fn or_vclone() {
     let ot = or_trigger::Trigger::new(format!("triggy"), left, right);
     let mut ot2 = ot.vclone();
     
-    assert_equal!(ot2.next().await, None);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(ot2.next(&ctx).await, None);
   });
-}
\ No newline at end of file
+}
+
+#[test]
+fn not_trigger_should_run() {
+  task::block_on(async {
+    let inner = Trigger::new(
+      format!("inner"),
+      Box::new(test_trigger::Trigger::new(format!("inner"), false, None)),
+    );
+
+    let mut nt = not_trigger::Trigger::new(format!("triggy"), inner);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(nt.evaluate(&ctx).await, true, "Not Trigger should run");
+  });
+}
+
+#[test]
+fn not_trigger_next() {
+  task::block_on(async {
+    let now = Utc::now();
+    let inner = Trigger::new(
+      format!("inner"),
+      Box::new(time_trigger::Trigger::new(
+        format!("inner"),
+        Some(5),
+        None,
+        Some(time_trigger::Time((now.hour() + 1) % 24, now.minute())),
+      )),
+    );
+
+    let mut nt = not_trigger::Trigger::new(format!("triggy"), inner);
+    let ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let ctx = context(&ledg);
+    assert_equal!(nt.evaluate(&ctx).await, true);
+    assert_equal!(
+      nt.next(&ctx).await,
+      Some(ctx.now + RECHECK_INTERVAL),
+      "Not Trigger should be rechecked rather than finish with its child"
+    );
+  });
+}
+
+#[test]
+fn not_trigger_should_run_with_ledger() {
+  task::block_on(async {
+    let time = Utc::now().timestamp_nanos();
+    let mut ledg =
+      Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+
+    ledg.ledger.insert(
+      &format!("store"),
+      &format!("job"),
+      &Status::Failure(format!("boom")),
+      &time,
+    );
+
+    let inner = Trigger::new(
+      format!("inner"),
+      Box::new(job_trigger::Trigger::new(
+        format!("inner"),
+        format!("job"),
+        format!("store"),
+        Status::Failure(format!("boom")),
+        time,
+      )),
+    );
+
+    let mut nt = not_trigger::Trigger::new(format!("triggy"), inner);
+    let ctx = context(&ledg);
+    assert_equal!(
+      nt.evaluate(&ctx).await,
+      false,
+      "Not Trigger shouldn't run when the job failed recently"
+    );
+  });
+}
@@ -0,0 +1,448 @@
+use async_std::task;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use k9::assert_equal;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use horoscope::executor::Executor;
+use horoscope::job::workflow::{step_alias, Workflow, RUN_ID};
+use horoscope::job::{sys, JobContext, Outcome, Status, Work};
+use horoscope::ledger::{memory, Ledger};
+use horoscope::scheduler::{blocking, Schedule};
+use horoscope::store::Store;
+
+// Outputs its name followed by the sorted outputs of its parents.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Concat {
+  name: String,
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Work for Concat {
+  async fn startup(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    Status::Success
+  }
+
+  async fn run(&self, ctx: &JobContext<'_>) -> Outcome {
+    let mut inputs: Vec<&String> = ctx.inputs.values().collect();
+    inputs.sort();
+    let inputs: Vec<String> = inputs.into_iter().cloned().collect();
+    Outcome::with_output(
+      Status::Success,
+      format!("{}({})", &self.name, inputs.join(",")),
+    )
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    Ok(())
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+fn concat(name: &str) -> Box<dyn Work> {
+  Box::new(Concat {
+    name: String::from(name),
+  })
+}
+
+// Lifecycle calls of every Hooked node, as "name:hook".
+static HOOKS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+fn hooks(name: &str) -> Vec<String> {
+  HOOKS
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|hook| hook.starts_with(&format!("{}:", name)))
+    .map(|hook| hook[name.len() + 1..].to_string())
+    .collect()
+}
+
+// Records its lifecycle calls, failing the hook named by `fail`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Hooked {
+  name: String,
+  fail: String,
+}
+
+impl Hooked {
+  fn call(&self, hook: &str) -> Result<(), String> {
+    HOOKS.lock().unwrap().push(format!("{}:{}", &self.name, hook));
+    if self.fail == hook {
+      Err(format!("{} failed", hook))
+    } else {
+      Ok(())
+    }
+  }
+}
+
+#[async_trait]
+#[typetag::serde(name = "WorkflowHooked")]
+impl Work for Hooked {
+  async fn startup(&self) -> Result<(), String> {
+    self.call("startup")
+  }
+
+  async fn before(&self, _ctx: &JobContext<'_>) -> Result<(), String> {
+    self.call("before")
+  }
+
+  async fn func(&self, _ctx: &JobContext<'_>) -> Status {
+    let _ = self.call("func");
+    Status::Success
+  }
+
+  async fn after(
+    &self,
+    _ctx: &JobContext<'_>,
+    _outcome: &Outcome,
+  ) -> Result<(), String> {
+    self.call("after")
+  }
+
+  async fn teardown(&self) -> Result<(), String> {
+    self.call("teardown")
+  }
+
+  fn vclone(&self) -> Box<dyn Work> {
+    Box::new(self.clone())
+  }
+}
+
+fn hooked(name: &str, fail: &str) -> Box<dyn Work> {
+  Box::new(Hooked {
+    name: String::from(name),
+    fail: String::from(fail),
+  })
+}
+
+fn shell(script: String) -> Box<dyn Work> {
+  let mut job = sys::Job::new(format!("shell"), script, vec![]);
+  job.shell = true;
+  Box::new(job)
+}
+
+#[test]
+fn workflow_add_node() {
+  let mut wf = Workflow::new(format!("wf"));
+  assert_equal!(wf.add_node(format!("a"), concat("a")), Ok(()));
+  assert_equal!(
+    wf.add_node(format!("a"), concat("a")),
+    Err(format!("Node a already exists in Workflow wf"))
+  );
+}
+
+#[test]
+fn workflow_add_edge() {
+  let mut wf = Workflow::new(format!("wf"));
+  wf.add_node(format!("a"), concat("a")).unwrap();
+  wf.add_node(format!("b"), concat("b")).unwrap();
+  wf.add_node(format!("c"), concat("c")).unwrap();
+
+  assert_equal!(
+    wf.add_edge(format!("a"), format!("z")),
+    Err(format!("Node z doesn't exist in Workflow wf"))
+  );
+  assert_equal!(wf.add_edge(format!("a"), format!("b")), Ok(()));
+  assert_equal!(wf.add_edge(format!("b"), format!("c")), Ok(()));
+  assert_equal!(
+    wf.add_edge(format!("c"), format!("a")),
+    Err(format!("Workflow wf has a cycle")),
+    "Edges closing a cycle should be rejected"
+  );
+  assert_equal!(wf.edges.len(), 2, "Rejected edges shouldn't be kept");
+  assert_equal!(wf.validate(), Ok(()));
+
+  wf.edges.push((format!("c"), format!("a")));
+  assert_equal!(wf.validate(), Err(format!("Workflow wf has a cycle")));
+}
+
+#[test]
+fn workflow_fan_out_fan_in() {
+  task::block_on(async {
+    let mut wf = Workflow::new(format!("wf"));
+    for node in &["root", "left", "right", "join"] {
+      wf.add_node(format!("{}", node), concat(node)).unwrap();
+    }
+    wf.add_edge(format!("root"), format!("left")).unwrap();
+    wf.add_edge(format!("root"), format!("right")).unwrap();
+    wf.add_edge(format!("left"), format!("join")).unwrap();
+    wf.add_edge(format!("right"), format!("join")).unwrap();
+
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx =
+      JobContext::new(&ledger, format!("store"), format!("wf"), now, now, 1);
+    let outcome = wf.run(&ctx).await;
+
+    assert_equal!(outcome.status, Status::Success);
+    let order: Vec<&String> = outcome.steps.iter().map(|(n, _)| n).collect();
+    assert_equal!(
+      order,
+      vec!["root", "left", "right", "join"],
+      "Nodes should run after their parents"
+    );
+    assert_equal!(
+      outcome.steps[3].1.output,
+      Some(format!("join(left(root()),right(root()))")),
+      "Fan-in nodes should see every parent's output"
+    );
+    let run_id = outcome.captures.get(RUN_ID).unwrap();
+    assert_equal!(run_id, &format!("wf-{}", now), "Run ids use the start");
+    for (_, step) in &outcome.steps {
+      assert_equal!(step.captures.get(RUN_ID), Some(run_id));
+    }
+  })
+}
+
+#[test]
+fn workflow_timeout_keeps_finished_steps() {
+  task::block_on(async {
+    let mut wf = Workflow::new(format!("wf"));
+    wf.add_node(format!("a"), concat("a")).unwrap();
+    wf.add_node(format!("b"), shell(format!("sleep 5"))).unwrap();
+    wf.add_edge(format!("a"), format!("b")).unwrap();
+    let work: Box<dyn Work> = Box::new(wf);
+
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx =
+      JobContext::new(&ledger, format!("store"), format!("wf"), now, now, 1);
+    let executor = Executor::new(format!("executor"));
+    let outcome = executor.run(&work, &ctx, Some(500000000)).await;
+
+    assert_equal!(outcome.status, Status::Failure(format!("timeout")));
+    assert_equal!(
+      outcome.captures.get(RUN_ID),
+      Some(&format!("wf-{}", now)),
+      "Timed out runs should keep their run id"
+    );
+    let order: Vec<&String> = outcome.steps.iter().map(|(n, _)| n).collect();
+    assert_equal!(order, vec!["a"], "Finished steps should be kept");
+    assert_equal!(outcome.steps[0].1.status, Status::Success);
+  })
+}
+
+#[test]
+fn workflow_failure_stops_descendants() {
+  task::block_on(async {
+    let mut wf = Workflow::new(format!("wf"));
+    wf.add_node(format!("a"), concat("a")).unwrap();
+    wf.add_node(format!("b"), shell(format!("exit 1"))).unwrap();
+    wf.add_node(format!("c"), concat("c")).unwrap();
+    wf.add_node(format!("d"), concat("d")).unwrap();
+    wf.add_edge(format!("a"), format!("b")).unwrap();
+    wf.add_edge(format!("b"), format!("c")).unwrap();
+    wf.add_edge(format!("a"), format!("d")).unwrap();
+
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx =
+      JobContext::new(&ledger, format!("store"), format!("wf"), now, now, 1);
+    assert_equal!(
+      wf.func(&ctx).await,
+      Status::Failure(format!("Workflow wf failed at [\"b\"]"))
+    );
+
+    let ran: Vec<String> = wf
+      .run(&ctx)
+      .await
+      .steps
+      .into_iter()
+      .map(|(node, _)| node)
+      .collect();
+    assert_equal!(
+      ran,
+      vec![format!("a"), format!("b"), format!("d")],
+      "Children of a failed node shouldn't run"
+    );
+  })
+}
+
+#[test]
+fn workflow_node_hooks() {
+  task::block_on(async {
+    let mut wf = Workflow::new(format!("wf"));
+    wf.add_node(format!("a"), hooked("hooks_a", "")).unwrap();
+    wf.add_node(format!("b"), hooked("hooks_b", "before")).unwrap();
+    wf.add_node(format!("c"), hooked("hooks_c", "after")).unwrap();
+    wf.add_edge(format!("a"), format!("b")).unwrap();
+    wf.add_edge(format!("a"), format!("c")).unwrap();
+
+    let ledger = Ledger::new(format!("horo"), Box::new(memory::Ledger::new()));
+    let now = Utc::now().timestamp_nanos();
+    let ctx =
+      JobContext::new(&ledger, format!("store"), format!("wf"), now, now, 1);
+    let outcome = wf.run(&ctx).await;
+    assert_equal!(hooks("hooks_a"), vec!["before", "func", "after"]);
+    assert_equal!(
+      hooks("hooks_b"),
+      vec!["before"],
+      "A failed before hook should skip the node"
+    );
+    assert_equal!(hooks("hooks_c"), vec!["before", "func", "after"]);
+    let statuses: Vec<Status> = outcome
+      .steps
+      .into_iter()
+      .map(|(_, step)| step.status)
+      .collect();
+    assert_equal!(
+      statuses,
+      vec![
+        Status::Success,
+        Status::Failure(format!(
+          "Before hook of Job {} failed: before failed",
+          step_alias(&format!("wf"), &format!("b"))
+        )),
+        Status::Failure(format!(
+          "After hook of Job {} failed: after failed",
+          step_alias(&format!("wf"), &format!("c"))
+        )),
+      ]
+    );
+  })
+}
+
+#[test]
+fn workflow_startup_failure_tears_down_started_nodes() {
+  task::block_on(async {
+    // Nodes start in map order, so a few workflows make sure some nodes start
+    // before the broken one.
+    let mut started = 0;
+    for attempt in 0..8 {
+      let names: Vec<String> = (0..3)
+        .map(|node| format!("start_{}_{}", attempt, node))
+        .collect();
+      let mut wf = Workflow::new(format!("wf"));
+      for name in &names {
+        wf.add_node(name.clone(), hooked(name, "")).unwrap();
+      }
+      let broken = format!("start_{}_broken", attempt);
+      wf.add_node(broken.clone(), hooked(&broken, "startup")).unwrap();
+      assert_equal!(wf.startup().await, Err(format!("startup failed")));
+      for name in &names {
+        let calls = hooks(name);
+        if calls.is_empty() {
+          continue;
+        }
+        started += 1;
+        assert_equal!(
+          calls,
+          vec!["startup", "teardown"],
+          "Started nodes should be torn down"
+        );
+      }
+    }
+    assert_equal!(started > 0, true);
+  })
+}
+
+#[test]
+fn workflow_retry_from_failed_node() {
+  task::block_on(async {
+    let dir = std::env::temp_dir().join("horoscope_workflow_retry");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let runs = dir.join("runs");
+    let marker = dir.join("marker");
+
+    let mut wf = Workflow::new(format!("wf"));
+    wf.add_node(
+      format!("extract"),
+      shell(format!("echo extract >> {}", runs.display())),
+    )
+    .unwrap();
+    // Fails the first time it runs, succeeds afterwards.
+    let load = format!(
+      "echo load >> {0}; [ -f {1} ] || {{ touch {1}; exit 1; }}",
+      runs.display(),
+      marker.display()
+    );
+    wf.add_node(format!("load"), shell(load.clone())).unwrap();
+    wf.add_edge(format!("extract"), format!("load")).unwrap();
+
+    let mut schdlr = blocking::Scheduler::new(String::from("scheduler"), None);
+    schdlr
+      .add_store(String::from("store"), Store::new(String::from("store")))
+      .await
+      .unwrap();
+    schdlr
+      .add_executor(
+        String::from("executor"),
+        Executor::new(String::from("executor")),
+      )
+      .await
+      .unwrap();
+    schdlr
+      .add_job(
+        String::from("wf"),
+        String::from("store"),
+        String::from("executor"),
+        Utc::now().timestamp_nanos() + 500000000000,
+        None,
+        Box::new(wf.clone()),
+      )
+      .await
+      .unwrap();
+
+    schdlr
+      .retry_run(format!("store"), format!("wf"), format!("run-1"))
+      .await
+      .unwrap();
+    let recorded = schdlr.ledger.ledger.outcomes(
+      &format!("store"),
+      &step_alias(&format!("wf"), &format!("load")),
+    );
+    assert_equal!(recorded.len(), 1, "Nodes should be recorded in the ledger");
+    assert_equal!(recorded[0].1.captures.get(RUN_ID), Some(&format!("run-1")));
+    assert_equal!(
+      wf.run_outcomes(
+        &schdlr.ledger,
+        &format!("store"),
+        &format!("wf"),
+        &format!("run-1")
+      )
+      .get("load")
+      .map(|outcome| outcome.status.clone()),
+      Some(Status::Failure(format!(
+        "Failed to successfully run {} with [], exit code 1",
+        load
+      )))
+    );
+
+    schdlr
+      .retry_run(format!("store"), format!("wf"), format!("run-1"))
+      .await
+      .unwrap();
+    let run = wf.run_outcomes(
+      &schdlr.ledger,
+      &format!("store"),
+      &format!("wf"),
+      &format!("run-1"),
+    );
+    assert_equal!(run.get("load").unwrap().status, Status::Success);
+    assert_equal!(
+      std::fs::read_to_string(&runs).unwrap(),
+      format!("extract\nload\nload\n"),
+      "Retrying should only run the failed node again"
+    );
+
+    let job = schdlr.stores.get("store").unwrap().jobs.get("wf").unwrap();
+    assert_equal!(&job.last_status, &Some(Status::Success));
+    assert_equal!(
+      schdlr
+        .retry_run(format!("store"), format!("nope"), format!("run-1"))
+        .await,
+      Err(format!("Failed to retry Job nope, it's not found in Store store"))
+    );
+  })
+}